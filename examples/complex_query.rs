@@ -7,7 +7,7 @@ use uuid::Uuid;
 
 use rustorm::query::{Query,QueryBuilder};
 use rustorm::query::Equality;
-use rustorm::dao::{Dao, IsDao};
+use rustorm::dao::{Dao, IsDao, DaoError};
 use rustorm::pool::ManagedPool;
 use rustorm::query::HasEquality;
 use rustorm::query::function::COUNT;
@@ -25,11 +25,11 @@ pub struct Photo {
 }
 
 impl IsDao for Photo{
-    fn from_dao(dao: &Dao) -> Self {
-        Photo {
-            photo_id: dao.get("photo_id"),
-            url: dao.get_opt("url"),
-        }
+    fn try_from_dao(dao: &Dao) -> Result<Self, DaoError> {
+        Ok(Photo {
+            photo_id: try!(dao.try_get("photo_id")),
+            url: try!(dao.try_get_opt("url")),
+        })
     }
 
     fn to_dao(&self) -> Dao {
@@ -69,7 +69,7 @@ fn main() {
          .GROUP_BY(&["category.name","category.id"])
          .HAVING(COUNT(&"*").GT(&1))
          .ORDER_BY(&["product.name".ASC()])
-         .build(db.as_ref());
+         .build(db.as_ref()).unwrap();
 
     let expected = "
 	   SELECT *
@@ -6,6 +6,7 @@ use chrono::naive::time::NaiveTime;
 use chrono::naive::datetime::NaiveDateTime;
 use chrono::offset::utc::UTC;
 use std::fmt;
+use std::error::Error;
 use query::ColumnName;
 use table::IsTable;
 use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
@@ -179,7 +180,7 @@ impl ToJson for Value {
             Value::F64(ref x) => x.to_json(),
             Value::String(ref x) => x.to_json(),
             Value::VecU8(ref x) => x.to_json(),
-            Value::Uuid(ref x) => x.to_hyphenated_string().to_json(),
+            Value::Uuid(ref x) => x.hyphenated().to_string().to_json(),
             Value::DateTime(ref x) => x.to_rfc3339().to_json(),
             //            Value::NaiveDate(ref x) => x.to_json(),
             //            Value::NaiveTime(ref x) => x.to_json(),
@@ -227,8 +228,21 @@ impl fmt::Display for Value {
 pub trait IsDao {
 
     /// convert dao to an instance of the corresponding struct of the model
-    /// taking into considerating the renamed columns
-    fn from_dao(dao: &Dao) -> Self;
+    /// taking into considerating the renamed columns,
+    /// panics on missing or mismatched values
+    fn from_dao(dao: &Dao) -> Self
+        where Self: Sized
+    {
+        match Self::try_from_dao(dao) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// convert dao to an instance of the corresponding struct of the model,
+    /// returning an error instead of panicking on missing or mismatched values.
+    /// Use `Dao::try_get` and `Dao::try_get_opt` to propagate the errors
+    fn try_from_dao(dao: &Dao) -> Result<Self, DaoError> where Self: Sized;
 
     /// convert from an instance of the struct to a dao representation
     /// to be saved into the database
@@ -251,11 +265,70 @@ pub trait ToCompact {
     fn compact_json(&self) -> Json;
 }
 
+/// description of a column in the result of a query
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct ColumnInfo {
+    /// the name of the column as returned, which is the alias when the column is renamed
+    pub name: String,
+    /// the table the column is selected from, when the database reports it.
+    /// Always None in postgresql, the driver does not report the source table of the columns
+    pub table: Option<String>,
+    /// the data type name as reported by the database
+    pub db_type: String,
+    /// the generic type the values of this column are converted to
+    pub data_type: Type,
+}
+
+/// the rows of a result in the order they are returned,
+/// each row has its values in the same order as the columns
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Rows {
+    pub columns: Vec<ColumnInfo>,
+    pub values: Vec<Vec<Value>>,
+}
+
+impl Rows {
+
+    pub fn new(columns: Vec<ColumnInfo>) -> Self {
+        Rows {
+            columns: columns,
+            values: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        self.values.push(row);
+    }
+
+    /// convert each row into a dao keyed by the column names,
+    /// when several columns have the same name, the value of the last one is kept
+    pub fn to_daos(&self) -> Vec<Dao> {
+        self.values.iter().map(|row| row_dao(&self.columns, row)).collect()
+    }
+}
+
+/// the values of the row keyed by the names of the columns,
+/// when several columns have the same name, the value of the last one is kept
+fn row_dao(columns: &[ColumnInfo], row: &[Value]) -> Dao {
+    let mut dao = Dao::new();
+    for (column, value) in columns.iter().zip(row.iter()) {
+        dao.set_value(&column.name, value.clone());
+    }
+    dao
+}
+
 /// meta result of a query useful when doing complex query, and also with paging
 /// TODO: good name: DaoRows
 #[derive(Debug)]
 pub struct DaoResult {
-    pub dao: Vec<Dao>,
+    /// the columns of the result in the order they are selected,
+    /// including the columns which have the same name
+    pub columns: Vec<ColumnInfo>,
+    /// the values of each row in the same order as the columns
+    pub rows: Vec<Vec<Value>>,
     ///renamed columns for each table
     /// ie. product => [(name, product_name),..];
     pub renamed_columns: Vec<(ColumnName, String)>,
@@ -284,7 +357,7 @@ impl SerDaoResult {
 
     pub fn from_dao_result(daoresult: &DaoResult) -> Self {
         SerDaoResult {
-            dao: daoresult.dao.clone(),
+            dao: daoresult.dao(),
             total: daoresult.total.clone(),
             page: daoresult.page.clone(),
             page_size: daoresult.page_size.clone(),
@@ -300,6 +373,28 @@ impl Encodable for DaoResult {
 }
 
 impl DaoResult {
+
+    /// the rows as daos keyed by the column names, built from the rows when called.
+    /// When several columns have the same name, the value of the last one is kept
+    pub fn dao(&self) -> Vec<Dao> {
+        self.rows.iter().map(|row| row_dao(&self.columns, row)).collect()
+    }
+
+    /// the position of the first column with this name
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// the values of the row at this position, in the order of the columns
+    pub fn row(&self, index: usize) -> Option<&[Value]> {
+        self.rows.get(index).map(|r| &r[..])
+    }
+
+    /// the value at this row and column position
+    pub fn get_value(&self, row: usize, column: usize) -> Option<&Value> {
+        self.rows.get(row).and_then(|r| r.get(column))
+    }
+
     /// get the list of renamed column name in matching table name
     fn get_renamed_columns(&self, table: &str) -> Vec<(String, String)> {
         let mut columns = vec![];
@@ -320,8 +415,8 @@ impl DaoResult {
         let non_nulls = table.non_nullable_columns();
         let mut obj = vec![];
         let renamed_columns = self.get_renamed_columns(&table.name);
-        for dao in &self.dao {
-            let mut dao_clone = dao.clone();
+        for row in &self.rows {
+            let mut dao_clone = row_dao(&self.columns, row);
             dao_clone.correct_renamed_columns(&renamed_columns);
             if dao_clone.all_has_values(&non_nulls) {
                 let p = T::from_dao(&dao_clone);
@@ -331,6 +426,24 @@ impl DaoResult {
         obj
     }
 
+    /// cast the dao to the specific struct instance using `try_from_dao`,
+    /// stops at the first dao that can not be converted
+    pub fn try_cast<T: IsTable + IsDao>(&self) -> Result<Vec<T>, DaoError> {
+        let table = T::table();
+        let non_nulls = table.non_nullable_columns();
+        let mut obj = vec![];
+        let renamed_columns = self.get_renamed_columns(&table.name);
+        for row in &self.rows {
+            let mut dao_clone = row_dao(&self.columns, row);
+            dao_clone.correct_renamed_columns(&renamed_columns);
+            if dao_clone.all_has_values(&non_nulls) {
+                let p = try!(T::try_from_dao(&dao_clone));
+                obj.push(p);
+            }
+        }
+        Ok(obj)
+    }
+
     /// FIXME: should return an error when there are more than 1 to be casted
     pub fn cast_one<T: IsTable + IsDao>(&self) -> Option<T> {
         let mut casted = self.cast::<T>();
@@ -463,31 +576,82 @@ impl Dao {
     pub fn remove<T>(&mut self, column: &str) -> T
         where T: FromValue
     {
-        let value = self.values.remove(column).unwrap();
-        FromValue::from_type(value)
+        match self.try_remove(column) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// take the value and remove the content,
+    /// the content is removed even when the value can not be converted
+    pub fn try_remove<T>(&mut self, column: &str) -> Result<T, DaoError>
+        where T: FromValue
+    {
+        match self.values.remove(column) {
+            Some(value) => FromValue::try_from_type(value).map_err(|e| DaoError::type_mismatch(column, e)),
+            None => Err(DaoError::NoSuchColumn(column.to_owned())),
+        }
     }
 
     /// take the value but not removing the content
     pub fn get<T>(&self, column: &str) -> T
         where T: FromValue
     {
-        let value = self.values.get(column).unwrap();
-        FromValue::from_type(value.clone())
+        match self.try_get(column) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// take the value but not removing the content,
+    /// returns an error when the column is missing or the value is of another type
+    pub fn try_get<T>(&self, column: &str) -> Result<T, DaoError>
+        where T: FromValue
+    {
+        let value = try!(self.try_as_ref(column));
+        FromValue::try_from_type(value.clone()).map_err(|e| DaoError::type_mismatch(column, e))
     }
+
     /// get optional value
     pub fn get_opt<T>(&self, column: &str) -> Option<T>
         where T: FromValue
+    {
+        match self.try_get_opt(column) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// get optional value, missing columns and nulls are None,
+    /// returns an error when the value is of another type
+    pub fn try_get_opt<T>(&self, column: &str) -> Result<Option<T>, DaoError>
+        where T: FromValue
     {
         let value = self.values.get(column);
         match value {
-            None | Some(&Value::None(_)) => None,
-            Some(v) => Some(FromValue::from_type(v.clone())),
+            None | Some(&Value::None(_)) => Ok(None),
+            Some(v) => {
+                FromValue::try_from_type(v.clone())
+                    .map(|x| Some(x))
+                    .map_err(|e| DaoError::type_mismatch(column, e))
+            }
         }
     }
 
     /// get a reference of the type
     pub fn as_ref(&self, column: &str) -> &Value {
-        self.values.get(column).unwrap()
+        match self.try_as_ref(column) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// get a reference of the type, returns an error when the column is missing
+    pub fn try_as_ref(&self, column: &str) -> Result<&Value, DaoError> {
+        match self.values.get(column) {
+            Some(value) => Ok(value),
+            None => Err(DaoError::NoSuchColumn(column.to_owned())),
+        }
     }
 
 
@@ -645,174 +809,371 @@ impl ToValue for Json {
         Value::Json(self.clone())
     }
 }
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-pub trait FromValue {
-    fn from_type(ty: Value) -> Self;
+
+/// The value could not be converted to the requested type
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct ConvertError {
+    pub expected: Type,
+    pub actual: Value,
 }
 
-impl FromValue for bool {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::Bool(x) => x,
-            _ => panic!("error!"),
-        }
+impl Error for ConvertError {
+    fn description(&self) -> &str {
+        "value can not be converted to the requested type"
     }
 }
 
-impl FromValue for i8 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I8(x) => x,
-            _ => panic!("error!"),
-        }
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expecting a {} but found {:?}", self.expected.to_str_repr(), self.actual)
     }
 }
-impl FromValue for i16 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I16(x) => x,
-            _ => panic!("error!"),
+
+/// Errors when extracting values out of a dao
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum DaoError {
+    /// the dao has no value for this column
+    NoSuchColumn(String),
+    /// the value of the column can not be converted to the expected type
+    TypeMismatch {
+        column: String,
+        expected: Type,
+        actual: Value,
+    },
+}
+
+impl DaoError {
+    fn type_mismatch(column: &str, err: ConvertError) -> Self {
+        DaoError::TypeMismatch {
+            column: column.to_owned(),
+            expected: err.expected,
+            actual: err.actual,
         }
     }
 }
-impl FromValue for i32 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I32(x) => x,
-            _ => panic!("error!"),
+
+impl Error for DaoError {
+    fn description(&self) -> &str {
+        match *self {
+            DaoError::NoSuchColumn(_) => "no such column",
+            DaoError::TypeMismatch{..} => "type mismatch",
         }
     }
 }
-impl FromValue for i64 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I64(x) => x,
-            _ => panic!("error!"),
+
+impl fmt::Display for DaoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DaoError::NoSuchColumn(ref column) => write!(f, "No such value for {}", column),
+            DaoError::TypeMismatch{ref column, ref expected, ref actual} => {
+                write!(f,
+                       "Column {} is expecting a {} but found {:?}",
+                       column,
+                       expected.to_str_repr(),
+                       actual)
+            }
         }
     }
 }
-impl FromValue for u8 {
+
+/// conversion of the generic value into rust types
+pub trait FromValue: Sized {
+
+    /// convert the value, returning an error when the value is of another type
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError>;
+
+    /// convert the value, panics when the value is of another type
     fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U8(x) => x,
-            _ => panic!("error!"),
+        match Self::try_from_type(ty) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
         }
     }
 }
-impl FromValue for u16 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U16(x) => x,
-            _ => panic!("error!"),
+
+macro_rules! impl_from_value {
+    ($ty:ty, $variant:ident) => (
+        impl FromValue for $ty {
+            fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+                match ty {
+                    Value::$variant(x) => Ok(x),
+                    _ => {
+                        Err(ConvertError {
+                            expected: Type::$variant,
+                            actual: ty,
+                        })
+                    }
+                }
+            }
         }
+    )
+}
+
+impl_from_value!(String, String);
+impl_from_value!(Vec<u8>, VecU8);
+impl_from_value!(Json, Json);
+
+/// the accepted textual representations of a boolean,
+/// sqlite and mysql have no boolean storage
+fn parse_bool(s: &str) -> Option<bool> {
+    match &*s.trim().to_lowercase() {
+        "true" | "t" | "yes" | "y" | "on" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "off" | "0" => Some(false),
+        _ => None,
     }
 }
-impl FromValue for u32 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U32(x) => x,
-            _ => panic!("error!"),
-        }
+
+impl FromValue for bool {
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::Bool(x) => Some(x),
+            Value::I8(x) if x == 0 || x == 1 => Some(x == 1),
+            Value::I16(x) if x == 0 || x == 1 => Some(x == 1),
+            Value::I32(x) if x == 0 || x == 1 => Some(x == 1),
+            Value::I64(x) if x == 0 || x == 1 => Some(x == 1),
+            Value::U8(x) if x == 0 || x == 1 => Some(x == 1),
+            Value::String(ref x) => parse_bool(x),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::Bool,
+            actual: ty,
+        })
     }
 }
-impl FromValue for u64 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U64(x) => x,
-            _ => panic!("error!"),
+
+/// integers are converted when the value fits in the requested type,
+/// widening always succeeds, narrowing is checked against the range
+macro_rules! impl_from_integer {
+    ($ty:ident, $variant:ident) => (
+        impl FromValue for $ty {
+            fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+                fn from_unsigned(x: u64) -> Option<$ty> {
+                    if x <= $ty::max_value() as u64 {
+                        Some(x as $ty)
+                    } else {
+                        None
+                    }
+                }
+                fn from_signed(x: i64) -> Option<$ty> {
+                    if x >= 0 {
+                        from_unsigned(x as u64)
+                    } else if x >= $ty::min_value() as i64 {
+                        Some(x as $ty)
+                    } else {
+                        None
+                    }
+                }
+                let converted = match ty {
+                    Value::I8(x) => from_signed(x as i64),
+                    Value::I16(x) => from_signed(x as i64),
+                    Value::I32(x) => from_signed(x as i64),
+                    Value::I64(x) => from_signed(x),
+                    Value::U8(x) => from_unsigned(x as u64),
+                    Value::U16(x) => from_unsigned(x as u64),
+                    Value::U32(x) => from_unsigned(x as u64),
+                    Value::U64(x) => from_unsigned(x),
+                    Value::String(ref x) => x.trim().parse::<$ty>().ok(),
+                    _ => None,
+                };
+                converted.ok_or(ConvertError {
+                    expected: Type::$variant,
+                    actual: ty,
+                })
+            }
         }
-    }
+    )
 }
 
+impl_from_integer!(i8, I8);
+impl_from_integer!(i16, I16);
+impl_from_integer!(i32, I32);
+impl_from_integer!(i64, I64);
+impl_from_integer!(u8, U8);
+impl_from_integer!(u16, U16);
+impl_from_integer!(u32, U32);
+impl_from_integer!(u64, U64);
+
 impl FromValue for f32 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::F32(x) => x,
-            _ => panic!("error!"),
-        }
+    /// only integers which are exactly representable are accepted,
+    /// f64 is accepted when it converts back to the same value
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::F32(x) => Some(x),
+            Value::F64(x) if x.is_nan() || (x as f32) as f64 == x => Some(x as f32),
+            Value::I8(x) => Some(x as f32),
+            Value::I16(x) => Some(x as f32),
+            Value::U8(x) => Some(x as f32),
+            Value::U16(x) => Some(x as f32),
+            Value::String(ref x) => x.trim().parse::<f32>().ok(),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::F32,
+            actual: ty,
+        })
     }
 }
+
 impl FromValue for f64 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::F64(x) => x,
-            _ => panic!("error! {:?}", ty),
-        }
+    /// only integers which are exactly representable are accepted
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::F64(x) => Some(x),
+            Value::F32(x) => Some(x as f64),
+            Value::I8(x) => Some(x as f64),
+            Value::I16(x) => Some(x as f64),
+            Value::I32(x) => Some(x as f64),
+            Value::U8(x) => Some(x as f64),
+            Value::U16(x) => Some(x as f64),
+            Value::U32(x) => Some(x as f64),
+            Value::String(ref x) => x.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::F64,
+            actual: ty,
+        })
     }
 }
 
-impl FromValue for String {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::String(x) => x,
-            _ => panic!("error! {:?}", ty),
-        }
+impl FromValue for Uuid {
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::Uuid(x) => Some(x),
+            Value::String(ref x) => Uuid::parse_str(x.trim()).ok(),
+            Value::VecU8(ref x) => Uuid::from_bytes(x).ok(),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::Uuid,
+            actual: ty,
+        })
     }
 }
 
-impl FromValue for Uuid {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::Uuid(x) => x,
-            _ => panic!("error!"),
+/// the formats used by the databases when timestamps are stored as text
+const DATETIME_FORMATS: [&'static str; 4] = ["%Y-%m-%d %H:%M:%S%.f",
+                                             "%Y-%m-%dT%H:%M:%S%.f",
+                                             "%Y-%m-%d %H:%M",
+                                             "%Y-%m-%dT%H:%M"];
+
+fn parse_naive_datetime(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    for format in DATETIME_FORMATS.iter() {
+        if let Ok(x) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(x);
         }
     }
+    None
 }
 
 impl FromValue for DateTime<UTC> {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::DateTime(x) => x,
-            _ => panic!("error!"),
-        }
+    /// timestamps without timezone are assumed to be in UTC
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::DateTime(x) => Some(x),
+            Value::NaiveDateTime(x) => Some(DateTime::from_utc(x, UTC)),
+            Value::String(ref x) => {
+                match DateTime::parse_from_rfc3339(x.trim()) {
+                    Ok(x) => Some(x.with_timezone(&UTC)),
+                    Err(_) => parse_naive_datetime(x).map(|x| DateTime::from_utc(x, UTC)),
+                }
+            }
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::DateTime,
+            actual: ty,
+        })
     }
 }
 
-impl FromValue for NaiveTime {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::NaiveTime(x) => x,
-            _ => panic!("error!"),
-        }
+impl FromValue for NaiveDateTime {
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::NaiveDateTime(x) => Some(x),
+            Value::DateTime(x) => Some(x.naive_utc()),
+            Value::String(ref x) => parse_naive_datetime(x),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::NaiveDateTime,
+            actual: ty,
+        })
     }
 }
 
 impl FromValue for NaiveDate {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::NaiveDate(x) => x,
-            _ => panic!("error!"),
-        }
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::NaiveDate(x) => Some(x),
+            Value::String(ref x) => NaiveDate::parse_from_str(x.trim(), "%Y-%m-%d").ok(),
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::NaiveDate,
+            actual: ty,
+        })
     }
 }
 
-impl FromValue for NaiveDateTime {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::NaiveDateTime(x) => x,
-            _ => panic!("error!"),
-        }
+impl FromValue for NaiveTime {
+    fn try_from_type(ty: Value) -> Result<Self, ConvertError> {
+        let converted = match ty {
+            Value::NaiveTime(x) => Some(x),
+            Value::String(ref x) => {
+                let x = x.trim();
+                NaiveTime::parse_from_str(x, "%H:%M:%S%.f")
+                    .or(NaiveTime::parse_from_str(x, "%H:%M"))
+                    .ok()
+            }
+            _ => None,
+        };
+        converted.ok_or(ConvertError {
+            expected: Type::NaiveTime,
+            actual: ty,
+        })
     }
 }
 
-impl FromValue for Json {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::Json(x) => x,
-            _ => panic!("error!"),
-        }
-    }
+#[test]
+fn test_widening() {
+    assert_eq!(i64::try_from_type(Value::I32(10)), Ok(10i64));
+    assert_eq!(i32::try_from_type(Value::U16(65535)), Ok(65535i32));
+    assert_eq!(f64::try_from_type(Value::F32(1.5)), Ok(1.5f64));
+}
+
+#[test]
+fn test_checked_narrowing() {
+    assert_eq!(i8::try_from_type(Value::I64(-128)), Ok(-128i8));
+    assert!(i8::try_from_type(Value::I64(128)).is_err());
+    assert!(u32::try_from_type(Value::I32(-1)).is_err());
+    assert_eq!(u64::try_from_type(Value::I64(1)), Ok(1u64));
+    assert!(i64::try_from_type(Value::U64(u64::max_value())).is_err());
+    assert_eq!(f32::try_from_type(Value::F64(0.5)), Ok(0.5f32));
+    assert!(f32::try_from_type(Value::F64(0.1)).is_err());
 }
 
+#[test]
+fn test_parse_from_string() {
+    assert_eq!(i32::try_from_type(Value::String("42".to_owned())), Ok(42));
+    assert_eq!(f64::try_from_type(Value::String("1.25".to_owned())), Ok(1.25));
+    assert_eq!(bool::try_from_type(Value::String("t".to_owned())), Ok(true));
+    assert_eq!(bool::try_from_type(Value::I64(0)), Ok(false));
+    let uuid = Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8").unwrap();
+    assert_eq!(Uuid::try_from_type(Value::String("936DA01F9ABD4d9d80C702AF85C822A8".to_owned())),
+               Ok(uuid));
+    let date = NaiveDate::from_ymd(2016, 2, 29);
+    assert_eq!(NaiveDate::try_from_type(Value::String("2016-02-29".to_owned())),
+               Ok(date));
+    assert_eq!(NaiveDateTime::try_from_type(Value::String("2016-02-29 10:30:00".to_owned())),
+               Ok(date.and_hms(10, 30, 0)));
+    assert_eq!(DateTime::<UTC>::try_from_type(Value::String("2016-02-29T10:30:00+02:00".to_owned())),
+               Ok(DateTime::from_utc(date.and_hms(8, 30, 0), UTC)));
+    assert!(i32::try_from_type(Value::String("apple".to_owned())).is_err());
+}
@@ -1,6 +1,6 @@
 use query::Query;
-use table::Table;
-use dao::{Dao, DaoResult, Value};
+use table::{Table, Column};
+use dao::{Dao, DaoResult, Value, DaoError, Rows};
 use writer::SqlFrag;
 use query::{Connector, Equality, Operand, Field};
 use query::{Direction, Modifier, NullsWhere, JoinType};
@@ -19,6 +19,14 @@ use rusqlite::SqliteError;
 use platform::PlatformError;
 use dao::Type;
 use query::source::{SourceField,QuerySource,ToSourceField};
+use query::{DeclaredQuery, TableName};
+use query::Error as QueryError;
+use query::{OnConflict, ConflictAction};
+use query::{Range, SetOperation};
+use query::Order;
+use query::ColumnName;
+use query::window::{WindowFunction, FrameUnit};
+use query::expression::{BinaryExpression, Operator};
 
 
 /// SqlOption, contains the info about the features and quirks of underlying database
@@ -39,6 +47,34 @@ pub enum SqlOption {
     /// wheter the returned rows in a query included Meta columns for easy extraction of records
     /// (postgres returns this), sqlite does not return meta columns, so you have to extract it by index yourself.
     ReturnMetaColumns,
+    /// supports `SELECT DISTINCT ON (columns)` (postgresql)
+    SupportsDistinctOn,
+    /// supports `INSERT .. ON CONFLICT (columns) DO UPDATE/DO NOTHING` (postgresql >= 9.5, sqlite >= 3.24)
+    SupportsOnConflict,
+    /// supports `INSERT OR REPLACE` and `INSERT OR IGNORE` (sqlite)
+    SupportsInsertOrReplace,
+    /// supports `INSERT .. ON DUPLICATE KEY UPDATE` (mysql)
+    SupportsOnDuplicateKey,
+    /// supports INTERSECT and EXCEPT in combining select queries (postgresql, sqlite)
+    SupportsIntersectExcept,
+    /// concatenates strings with the CONCAT() function instead of the `||` operator (mysql)
+    UsesConcatFunction,
+    /// DDL statements can be rolled back as part of a transaction (postgresql, sqlite)
+    SupportsTransactionalDDL,
+    /// `DEFAULT` can be used in the values of an insert (postgresql, mysql)
+    SupportsDefaultKeyword,
+    /// supports functions applied `OVER` a window (postgresql, mysql >= 8.0, sqlite >= 3.25)
+    SupportsWindowFunctions,
+}
+
+/// the rows of a query, read from the database as the iterator advances
+pub type DaoStream<'a> = Box<Iterator<Item = Result<Dao, DbError>> + 'a>;
+
+/// a statement prepared to stream its rows.
+/// The rows borrow the statement, so it is kept by the caller for as long as the rows are read
+pub trait StreamStatement {
+    /// execute the statement, the rows are returned one at a time as they are read from the database
+    fn rows<'s>(&'s mut self) -> Result<DaoStream<'s>, DbError>;
 }
 
 /// specifies if the sql will be build in debug mode for debugging purposed
@@ -51,17 +87,93 @@ pub enum BuildMode{
 	Standard,
 }
 
+/// The database agnostic kind of an error,
+/// mapped from the SQLSTATE, result code or error number of the underlying database
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum ErrorKind {
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    /// the transaction could not be serialized, it can be retried
+    SerializationFailure,
+    /// the transaction was chosen as the deadlock victim, it can be retried
+    Deadlock,
+    /// a lock held by another connection was not released in time,
+    /// the statement can be retried but it may wait for the same lock again
+    LockTimeout,
+    /// the connection to the database is lost or could not be established
+    ConnectionLost,
+    SyntaxError,
+    UndefinedTable,
+    UndefinedColumn,
+    Other,
+}
+
+impl ErrorKind {
+
+    /// the kind of error from a standard SQLSTATE code
+    pub fn from_sqlstate(code: &str) -> Self {
+        match code {
+            "23505" => ErrorKind::UniqueViolation,
+            "23503" => ErrorKind::ForeignKeyViolation,
+            "23502" => ErrorKind::NotNullViolation,
+            "23514" => ErrorKind::CheckViolation,
+            "40001" => ErrorKind::SerializationFailure,
+            "40P01" => ErrorKind::Deadlock,
+            "55P03" => ErrorKind::LockTimeout,
+            "57P01" | "57P02" | "57P03" => ErrorKind::ConnectionLost,
+            "42601" => ErrorKind::SyntaxError,
+            "42P01" => ErrorKind::UndefinedTable,
+            "42703" => ErrorKind::UndefinedColumn,
+            _ if code.starts_with("08") => ErrorKind::ConnectionLost,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DbError {
     Error(String),
     PoolError(r2d2::InitializationError),
     PlatformError(PlatformError),
+    /// the query can not be built
+    QueryError(QueryError),
+    /// the values of the dao can not be extracted
+    DaoError(DaoError),
 }
 
 impl DbError {
     pub fn new(description: &str) -> Self {
         DbError::Error(description.to_owned())
     }
+
+    /// the database agnostic kind of this error
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            DbError::PoolError(_) => ErrorKind::ConnectionLost,
+            DbError::PlatformError(ref err) => err.kind(),
+            DbError::Error(_) | DbError::QueryError(_) | DbError::DaoError(_) => ErrorKind::Other,
+        }
+    }
+
+    /// the name of the violated constraint, when provided by the database
+    pub fn constraint(&self) -> Option<String> {
+        match *self {
+            DbError::PlatformError(ref err) => err.constraint(),
+            _ => None,
+        }
+    }
+
+    /// the name of the table involved in the error, when provided by the database
+    pub fn table(&self) -> Option<String> {
+        match *self {
+            DbError::PlatformError(ref err) => err.table(),
+            _ => None,
+        }
+    }
 }
 
 impl Error for DbError {
@@ -70,6 +182,8 @@ impl Error for DbError {
             DbError::Error(ref description) => description,
             DbError::PoolError(ref err) => err.description(),
             DbError::PlatformError(ref err) => err.description(),
+            DbError::QueryError(ref err) => err.description(),
+            DbError::DaoError(ref err) => err.description(),
         }
     }
 
@@ -78,6 +192,8 @@ impl Error for DbError {
             DbError::Error(_) => None,
             DbError::PoolError(ref err) => Some(err),
             DbError::PlatformError(ref err) => Some(err),
+            DbError::QueryError(ref err) => Some(err),
+            DbError::DaoError(ref err) => Some(err),
         }
     }
 }
@@ -86,7 +202,9 @@ impl fmt::Display for DbError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             DbError::PoolError(ref err) => write!(f, "Pool error: {}", err),
-            DbError::PlatformError(ref err) => write!(f, "PostgreSQL error: {}", err),
+            DbError::PlatformError(ref err) => write!(f, "{}", err),
+            DbError::QueryError(ref err) => write!(f, "Query error: {}", err),
+            DbError::DaoError(ref err) => write!(f, "Dao error: {}", err),
             DbError::Error(_) => write!(f, "{}", self.description()),
         }
     }
@@ -104,6 +222,18 @@ impl From<PlatformError> for DbError {
     }
 }
 
+impl From<QueryError> for DbError {
+    fn from(err: QueryError) -> Self {
+        DbError::QueryError(err)
+    }
+}
+
+impl From<DaoError> for DbError {
+    fn from(err: DaoError) -> Self {
+        DbError::DaoError(err)
+    }
+}
+
 impl From<RegexError> for DbError {
     fn from(err: RegexError) -> Self {
         DbError::new(err.description())
@@ -151,16 +281,19 @@ pub trait Database {
     /// lower version of database has fewer supported features
     fn version(&self) -> Result<String, DbError>;
 
-    /// begin database transaction
-    fn begin(&self);
+    /// begin database transaction,
+    /// when there is already an active transaction, a savepoint is created instead
+    fn begin(&self) -> Result<(), DbError>;
 
-    /// commit database transaction
-    fn commit(&self);
+    /// commit database transaction,
+    /// releases the savepoint when called inside a nested transaction
+    fn commit(&self) -> Result<(), DbError>;
 
-    /// rollback data changes executed prior to calling the begin method
-    fn rollback(&self);
+    /// rollback data changes executed prior to calling the begin method,
+    /// rolls back to the savepoint when called inside a nested transaction
+    fn rollback(&self) -> Result<(), DbError>;
 
-    /// determine if this transaction has been committed or rolledback
+    /// determine if there is an active transaction that has not yet been committed or rolledback
     fn is_transacted(&self) -> bool;
 
     /// determine if the database connection closed
@@ -188,7 +321,7 @@ pub trait Database {
     /// insert an object, returns the inserted Dao value
     /// including the value generated via the defaults
     fn insert(&self, query: &Query) -> Result<Dao, DbError> {
-        let sql_frag = self.build_insert(query, BuildMode::Standard);
+        let sql_frag = try!(self.build_insert(query, BuildMode::Standard));
         match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
             Ok(Some(result)) => Ok(result),
             Ok(None) => Err(DbError::new("No result from insert")),
@@ -206,11 +339,27 @@ pub trait Database {
 
     /// execute query with return dao,
     /// use the enumerated column for data extraction when db doesn't support returning the records column names
+    /// inserts with more parameters than the database allows are executed in batches,
+    /// in a transaction when there is no active transaction so no batch is kept when one fails
     fn execute_with_return(&self, query: &Query) -> Result<DaoResult, DbError> {
-        let sql_frag = &self.build_query(query, BuildMode::Standard);
-        let result = try!(self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params));
+        let batches = try!(self.batches(query, self.max_parameters()));
+        let result = try!(all_or_nothing(self, batches.len() > 1, || {
+            let mut columns = vec![];
+            let mut rows = vec![];
+            for batch in &batches {
+                let sql_frag = &try!(self.build_query(batch, BuildMode::Standard));
+                let mut batch_result = try!(self.execute_sql_with_columns(&sql_frag.sql, &sql_frag.params));
+                columns = batch_result.columns;
+                rows.append(&mut batch_result.values);
+            }
+            Ok(Rows {
+                columns: columns,
+                values: rows,
+            })
+        }));
         let dao_result = DaoResult {
-            dao: result,
+            columns: result.columns,
+            rows: result.values,
             renamed_columns: query.get_renamed_columns(),
             total: None,
             page: None,
@@ -221,18 +370,134 @@ pub trait Database {
 
     /// execute query with 1 return dao
     fn execute_with_one_return(&self, query: &Query) -> Result<Option<Dao>, DbError> {
-        let sql_frag = &self.build_query(query, BuildMode::Standard);
+        let sql_frag = &try!(self.build_query(query, BuildMode::Standard));
         self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params)
     }
 
     /// execute query with no return dao
+    /// inserts with more parameters than the database allows are executed in batches,
+    /// in a transaction when there is no active transaction so no batch is kept when one fails
     fn execute(&self, query: &Query) -> Result<usize, DbError> {
-        let sql_frag = &self.build_query(query, BuildMode::Standard);
-        self.execute_sql(&sql_frag.sql, &sql_frag.params)
+        let batches = try!(self.batches(query, self.max_parameters()));
+        all_or_nothing(self, batches.len() > 1, || {
+            let mut affected = 0;
+            for batch in &batches {
+                let sql_frag = &try!(self.build_query(batch, BuildMode::Standard));
+                affected += try!(self.execute_sql(&sql_frag.sql, &sql_frag.params));
+            }
+            Ok(affected)
+        })
+    }
+
+    /// split an insert of several rows into inserts which each use
+    /// no more than `max_parameters` parameters, including the parameters outside of the values.
+    /// Where the DEFAULT keyword is not supported, consecutive rows with defaults
+    /// at the same columns are inserted together without those columns.
+    /// Other queries are returned as is.
+    fn batches(&self, query: &Query, max_parameters: usize) -> Result<Vec<Query>, QueryError> {
+        let columns = query.enumerated_fields.len();
+        if query.sql_type != SqlType::INSERT || columns == 0 || query.values.len() % columns != 0 {
+            return Ok(vec![query.clone()]);
+        }
+        let supports_default = self.sql_options().contains(&SqlOption::SupportsDefaultKeyword);
+        let mut runs: Vec<(Vec<bool>, Vec<&[Operand]>)> = vec![];
+        for row in query.values.chunks(columns) {
+            let defaults: Vec<bool> = row.iter()
+                                         .map(|v| match *v {
+                                             Operand::Default => !supports_default,
+                                             _ => false,
+                                         })
+                                         .collect();
+            let same_run = match runs.last() {
+                Some(&(ref run_defaults, _)) => *run_defaults == defaults,
+                None => false,
+            };
+            if same_run {
+                runs.last_mut().unwrap().1.push(row);
+            } else {
+                runs.push((defaults, vec![row]));
+            }
+        }
+        let mut batches = vec![];
+        for (defaults, rows) in runs {
+            let mut template = query.clone();
+            template.values = vec![];
+            template.enumerated_fields = query.enumerated_fields
+                                              .iter()
+                                              .zip(defaults.iter())
+                                              .filter(|&(_, default)| !*default)
+                                              .map(|(field, _)| field.clone())
+                                              .collect();
+            if template.enumerated_fields.is_empty() {
+                return Err(QueryError::ValuesMismatch(
+                    "a row with only default values can not be inserted in this database platform".to_owned()));
+            }
+            let rows: Vec<Vec<Operand>> = rows.iter()
+                                              .map(|row| {
+                                                  row.iter()
+                                                     .zip(defaults.iter())
+                                                     .filter(|&(_, default)| !*default)
+                                                     .map(|(value, _)| value.clone())
+                                                     .collect()
+                                              })
+                                              .collect();
+            let mut row_parameters = vec![];
+            for row in &rows {
+                let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+                for value in row {
+                    try!(self.build_operand(&mut w, &template, value));
+                }
+                row_parameters.push(w.params.len());
+            }
+            // the parameters of the filters, declared queries and returning clause
+            let mut first = template.clone();
+            first.values = rows[0].clone();
+            let fixed_parameters = try!(self.build_query(&first, BuildMode::Standard)).params.len() - row_parameters[0];
+
+            let mut batch = template.clone();
+            let mut parameters = fixed_parameters;
+            for (row, row_parameter) in rows.into_iter().zip(row_parameters) {
+                if fixed_parameters + row_parameter > max_parameters {
+                    return Err(QueryError::InvalidQuery(
+                        format!("a row needing {} parameters can not be inserted, the database allows only {} parameters in a statement",
+                                fixed_parameters + row_parameter, max_parameters)));
+                }
+                if parameters + row_parameter > max_parameters {
+                    batches.push(batch);
+                    batch = template.clone();
+                    parameters = fixed_parameters;
+                }
+                parameters += row_parameter;
+                batch.values.extend(row);
+            }
+            batches.push(batch);
+        }
+        Ok(batches)
     }
 
     /// execute insert with returning clause, update with returning clause
-    fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError>;
+    fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError> {
+        let rows = try!(self.execute_sql_with_columns(sql, params));
+        Ok(rows.to_daos())
+    }
+
+    /// execute the sql and return the rows with the description of the columns,
+    /// the values of each row are in the order the columns are selected
+    fn execute_sql_with_columns(&self, sql: &str, params: &[Value]) -> Result<Rows, DbError>;
+
+    /// execute several statements separated with `;`, such as a DDL script.
+    /// No parameters are allowed and nothing is returned
+    fn execute_batch(&self, sql: &str) -> Result<(), DbError>;
+
+    /// prepare the sql for its rows to be returned one at a time as they are read from the database,
+    /// instead of collecting all of them in memory first
+    fn stream_sql<'a>(&'a self, sql: &str, params: &[Value]) -> Result<Box<StreamStatement + 'a>, DbError>;
+
+    /// prepare the query for its rows to be returned one at a time as they are read from the database
+    fn stream<'a>(&'a self, query: &Query) -> Result<Box<StreamStatement + 'a>, DbError> {
+        let sql_frag = try!(self.build_query(query, BuildMode::Standard));
+        self.stream_sql(&sql_frag.sql, &sql_frag.params)
+    }
 
     fn execute_sql_with_one_return(&self,
                                    sql: &str,
@@ -252,8 +517,7 @@ pub trait Database {
     /// build a query, return the sql string and the parameters.
     /// use by select to build the select query
     /// build all types of query
-    /// TODO: need to supply the number of parameters where to start the numbering of the number parameters
-    fn build_query(&self, query: &Query, build_mode: BuildMode) -> SqlFrag {
+    fn build_query(&self, query: &Query, build_mode: BuildMode) -> Result<SqlFrag, QueryError> {
         match query.sql_type {
             SqlType::SELECT => self.build_select(query, build_mode),
             SqlType::INSERT => self.build_insert(query, build_mode),
@@ -262,18 +526,29 @@ pub trait Database {
         }
     }
 
+    /// write the query into an existing sql fragment,
+    /// used for nested queries so the parameters continues from the parent statement
+    fn build_query_into(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        match query.sql_type {
+            SqlType::SELECT => self.build_select_into(w, query),
+            SqlType::INSERT => self.build_insert_into(w, query),
+            SqlType::UPDATE => self.build_update_into(w, query),
+            SqlType::DELETE => self.build_delete_into(w, query),
+        }
+    }
+
     /// build operand, i.e: columns, query, function, values
-    fn build_operand(&self, w: &mut SqlFrag, parent_query: &Query, operand: &Operand) {
+    fn build_operand(&self, w: &mut SqlFrag, parent_query: &Query, operand: &Operand) -> Result<(), QueryError> {
         match *operand {
             Operand::ColumnName(ref column_name) => {
-                if parent_query.joins.is_empty() {
+                if parent_query.joins.is_empty() && !self.is_outer_column(w, parent_query, column_name) {
                     w.append(&column_name.column);
                 } else {
                     w.append(&column_name.complete_name());
                 }
             }
 			Operand::QuerySource(ref query_source) => {
-				self.build_query_source(w, parent_query, query_source);
+				try!(self.build_query_source(w, parent_query, query_source));
 			}
             Operand::Value(ref value) => {
                 w.parameter(value.clone());
@@ -288,57 +563,210 @@ pub trait Database {
                         } else {
                             do_comma = true;
                         }
-                        self.build_operand(w, parent_query, op);
+                        try!(self.build_operand(w, parent_query, op));
                     }
                     w.append(")");
                 }
             }
+            Operand::Window(ref window_function) => {
+                try!(self.build_window_function(w, parent_query, window_function));
+            }
+            Operand::Binary(ref binary) => {
+                try!(self.build_binary_expression(w, parent_query, binary));
+            }
+            Operand::Negate(ref operand) => {
+                w.append("-");
+                try!(self.build_nested_operand(w, parent_query, operand));
+            }
+            Operand::Case(ref case) => {
+                w.append("CASE");
+                for &(ref filter, ref result) in &case.when_then {
+                    w.append(" WHEN ");
+                    try!(self.build_filter(w, parent_query, filter));
+                    w.append("THEN ");
+                    try!(self.build_operand(w, parent_query, result));
+                }
+                if let Some(ref result) = case.else_result {
+                    w.append(" ELSE ");
+                    try!(self.build_operand(w, parent_query, result));
+                }
+                w.append(" END");
+            }
+            Operand::Default => {
+                if !self.sql_options().contains(&SqlOption::SupportsDefaultKeyword) {
+                    return Err(QueryError::UnsupportedFeature(
+                        "DEFAULT values are not supported in this database platform".to_owned()));
+                }
+                w.append("DEFAULT");
+            }
+        }
+        Ok(())
+    }
+
+    /// build left operator right, concatenation is CONCAT(left, right) in databases such as mysql
+    fn build_binary_expression(&self, w: &mut SqlFrag, parent_query: &Query, binary: &BinaryExpression) -> Result<(), QueryError> {
+        if binary.operator == Operator::CONCAT && self.sql_options().contains(&SqlOption::UsesConcatFunction) {
+            w.append("CONCAT(");
+            try!(self.build_operand(w, parent_query, &binary.left));
+            w.commasp();
+            try!(self.build_operand(w, parent_query, &binary.right));
+            w.append(")");
+        } else {
+            try!(self.build_nested_operand(w, parent_query, &binary.left));
+            w.append(&format!(" {} ", binary.operator));
+            try!(self.build_nested_operand(w, parent_query, &binary.right));
+        }
+        Ok(())
+    }
+
+    /// expressions used as operand of other expressions are enclosed in parenthesis
+    fn build_nested_operand(&self, w: &mut SqlFrag, parent_query: &Query, operand: &Operand) -> Result<(), QueryError> {
+        match *operand {
+            Operand::Binary(_) | Operand::Negate(_) => {
+                w.append("(");
+                try!(self.build_operand(w, parent_query, operand));
+                w.append(")");
+            }
+            _ => try!(self.build_operand(w, parent_query, operand)),
+        }
+        Ok(())
+    }
+
+    /// whether the column refers to a table of the outer query, as in correlated subqueries,
+    /// these columns needs to be written with the table name
+    fn is_outer_column(&self, w: &SqlFrag, query: &Query, column_name: &ColumnName) -> bool {
+        if w.subquery_depth == 0 {
+            return false;
+        }
+        match column_name.table {
+            Some(ref table) => !query.get_involved_tables().iter().any(|t| &t.name == table),
+            None => false,
+        }
+    }
+
+    /// build function OVER (PARTITION BY .. ORDER BY .. frame)
+    fn build_window_function(&self, w: &mut SqlFrag, parent_query: &Query, window_function: &WindowFunction) -> Result<(), QueryError> {
+        if !self.sql_options().contains(&SqlOption::SupportsWindowFunctions) {
+            return Err(QueryError::UnsupportedFeature(
+                "Window functions are not supported in this database platform".to_owned()));
+        }
+        match window_function.function {
+            Operand::QuerySource(QuerySource::Function(_)) => {
+                try!(self.build_operand(w, parent_query, &window_function.function));
+            }
+            _ => return Err(QueryError::InvalidQuery("Only functions can be used with OVER".to_owned())),
+        }
+        w.append(" OVER (");
+        let window = &window_function.window;
+        let mut do_space = false;
+        if !window.partition_by.is_empty() {
+            w.append("PARTITION BY ");
+            let mut do_comma = false;
+            for operand in &window.partition_by {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                try!(self.build_operand(w, parent_query, operand));
+            }
+            do_space = true;
         }
+        if !window.order_by.is_empty() {
+            if do_space {
+                w.sp();
+            }
+            w.append("ORDER BY ");
+            let mut do_comma = false;
+            for order in &window.order_by {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                try!(self.build_order(w, parent_query, order));
+            }
+            do_space = true;
+        }
+        if let Some(ref frame) = window.frame {
+            if do_space {
+                w.sp();
+            }
+            match frame.unit {
+                FrameUnit::ROWS => w.append("ROWS "),
+                FrameUnit::RANGE => w.append("RANGE "),
+            };
+            match frame.end {
+                Some(ref end) => {
+                    w.append("BETWEEN ");
+                    w.append(&frame.start.to_string());
+                    w.append(" AND ");
+                    w.append(&end.to_string());
+                }
+                None => {
+                    w.append(&frame.start.to_string());
+                }
+            }
+        }
+        w.append(")");
+        Ok(())
     }
 
-    fn build_condition(&self, w: &mut SqlFrag, parent_query: &Query, cond: &Condition) {
-        self.build_operand(w, parent_query, &cond.left);
+    fn build_condition(&self, w: &mut SqlFrag, parent_query: &Query, cond: &Condition) -> Result<(), QueryError> {
+        // the subquery is on the left, there is no right operand
+        match cond.equality {
+            Equality::EXISTS => {
+                w.append("EXISTS ");
+                return self.build_operand(w, parent_query, &cond.left);
+            }
+            Equality::NOT_EXISTS => {
+                w.append("NOT EXISTS ");
+                return self.build_operand(w, parent_query, &cond.left);
+            }
+            _ => (),
+        }
+        try!(self.build_operand(w, parent_query, &cond.left));
         w.append(" ");
         match cond.equality {
             Equality::EQ => {
                 w.append("= ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::NEQ => {
                 w.append("!= ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::LT => {
                 w.append("< ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::LTE => {
                 w.append("<= ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::GT => {
                 w.append("> ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::GTE => {
                 w.append(">= ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::IN => {
                 w.append("IN ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::NOT_IN => {
                 w.append("NOT IN ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::LIKE => {
                 w.append("LIKE ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::ILIKE => {
                 w.append("ILIKE ");
-                self.build_operand(w, parent_query, &cond.right);
+                try!(self.build_operand(w, parent_query, &cond.right));
             }
             Equality::IS_NOT_NULL => {
                 w.append("IS NOT NULL");
@@ -347,11 +775,13 @@ pub trait Database {
             Equality::IS_NULL => {
                 w.append("IS NULL");
             }
+            Equality::EXISTS | Equality::NOT_EXISTS => unreachable!(),
         }
+        Ok(())
     }
 
-    fn build_field(&self, w: &mut SqlFrag, parent_query: &Query, field: &Field) {
-        self.build_operand(w, parent_query, &field.operand);
+    fn build_field(&self, w: &mut SqlFrag, parent_query: &Query, field: &Field) -> Result<(), QueryError> {
+        try!(self.build_operand(w, parent_query, &field.operand));
         match field.name {
             Some(ref name) => {
                 w.append(" AS ");
@@ -359,11 +789,15 @@ pub trait Database {
             }
             None => (),
         }
+        Ok(())
     }
 	
-    fn build_query_source(&self, w: &mut SqlFrag, parent_query: &Query, query_source: &QuerySource) {
+    fn build_query_source(&self, w: &mut SqlFrag, parent_query: &Query, query_source: &QuerySource) -> Result<(), QueryError> {
 		match *query_source{
             QuerySource::TableName(ref table_name) => {
+                if let Some(declared) = self.get_inline_declared_query(parent_query, table_name) {
+                    return self.build_inline_declared_query(w, parent_query, declared);
+                }
                 if self.sql_options().contains(&SqlOption::UsesSchema) {
                     w.append(&table_name.complete_name());
                 } else {
@@ -381,19 +815,45 @@ pub trait Database {
                     } else {
                         do_comma = true;
                     }
-                    self.build_operand(w, parent_query, param);
+                    try!(self.build_operand(w, parent_query, param));
                 }
                 w.append(")");
             }
-            QuerySource::Query(ref _q) => {
-                let sql_frag = &self.build_query(&_q, w.build_mode.clone());
-                w.append(&sql_frag.sql);
+            QuerySource::Query(ref query) => {
+                let inherited = self.inherit_declared_query(parent_query, query);
+                w.append("(");
+                try!(self.build_subquery(w, inherited.as_ref().unwrap_or(query)));
+                w.append(")");
             }
 		}
+        Ok(())
 	}
 
-    fn build_source_field(&self, w: &mut SqlFrag, parent_query: &Query, source_field: &SourceField) {
-        self.build_query_source(w, parent_query, &source_field.source);
+    /// write the select query nested in the statement,
+    /// the parameters are continued from the parent statement
+    fn build_subquery(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        if query.sql_type != SqlType::SELECT {
+            return Err(QueryError::InvalidQuery("Only select queries can be used as subquery".to_owned()));
+        }
+        w.subquery_depth += 1;
+        try!(self.build_select_into(w, query));
+        w.subquery_depth -= 1;
+        Ok(())
+    }
+
+    fn build_source_field(&self, w: &mut SqlFrag, parent_query: &Query, source_field: &SourceField) -> Result<(), QueryError> {
+        if let QuerySource::TableName(ref table_name) = source_field.source {
+            if let Some(declared) = self.get_inline_declared_query(parent_query, table_name) {
+                try!(self.build_inline_declared_query(w, parent_query, declared));
+                w.append(" AS ");
+                match source_field.rename {
+                    Some(ref rename) => w.append(rename),
+                    None => w.append(&declared.name),
+                };
+                return Ok(());
+            }
+        }
+        try!(self.build_query_source(w, parent_query, &source_field.source));
         match source_field.rename {
             Some(ref rename) => {
                 w.append(" AS ");
@@ -401,13 +861,110 @@ pub trait Database {
             }
             None => (),
         }
+        Ok(())
+    }
+
+    /// build the WITH clause of the declared queries
+    /// when the database doesn't support CTE, nothing is written here,
+    /// the declared queries are inlined as subqueries where they are referred to instead
+    fn build_with(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        if query.declared_query.is_empty() {
+            return Ok(());
+        }
+        if !self.sql_options().contains(&SqlOption::SupportsCTE) {
+            // only a select can be inlined, the table modified by the statement can't be a subquery
+            if query.sql_type != SqlType::SELECT {
+                if let Some(table_name) = query.get_from_table() {
+                    if self.get_inline_declared_query(query, &table_name).is_some() {
+                        return Err(QueryError::UnsupportedFeature(
+                            format!("Declared query {} can not be modified in this database platform",
+                                    table_name.name)));
+                    }
+                }
+            }
+            return Ok(());
+        }
+        w.left_river("WITH");
+        if query.declared_query.iter().any(|declared| declared.is_recursive()) {
+            w.append("RECURSIVE ");
+        }
+        let mut do_comma = false;
+        for declared in &query.declared_query {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            w.append(&declared.name);
+            w.append(" AS (");
+            try!(self.build_query_into(w, &declared.query));
+            if let Some(ref recursive) = declared.recursive {
+                w.left_river("UNION ALL");
+                try!(self.build_query_into(w, recursive));
+            }
+            w.ln();
+            w.append(")");
+        }
+        Ok(())
     }
 
-    fn build_filter(&self, w: &mut SqlFrag, parent_query: &Query, filter: &Filter) {
+    /// the nested query together with the declared queries of the enclosing query,
+    /// so the references to them in the nested query are inlined too.
+    /// None when the nested query can be used as is
+    fn inherit_declared_query(&self, parent_query: &Query, query: &Query) -> Option<Query> {
+        if parent_query.declared_query.is_empty() || self.sql_options().contains(&SqlOption::SupportsCTE) {
+            return None;
+        }
+        // the queries declared in the nested query takes precedence over the inherited ones with the same name
+        let mut declared_query: Vec<DeclaredQuery> = parent_query.declared_query
+                                                                 .iter()
+                                                                 .filter(|d| query.get_declared_query(&d.name).is_none())
+                                                                 .cloned()
+                                                                 .collect();
+        if declared_query.is_empty() {
+            return None;
+        }
+        let mut query = query.clone();
+        declared_query.extend(query.declared_query.drain(..));
+        query.declared_query = declared_query;
+        Some(query)
+    }
+
+    /// the declared query refered to by this table name which needs to be inlined as a subquery,
+    /// only when the database doesn't support CTE
+    fn get_inline_declared_query<'a>(&self, parent_query: &'a Query, table_name: &TableName) -> Option<&'a DeclaredQuery> {
+        if table_name.schema.is_none() && !self.sql_options().contains(&SqlOption::SupportsCTE) {
+            parent_query.get_declared_query(&table_name.name)
+        } else {
+            None
+        }
+    }
+
+    /// write the declared query as a subquery, used in databases that doesn't support CTE
+    fn build_inline_declared_query(&self, w: &mut SqlFrag, parent_query: &Query, declared: &DeclaredQuery) -> Result<(), QueryError> {
+        if declared.is_recursive() {
+            return Err(QueryError::UnsupportedFeature(
+                format!("Recursive query {} is not supported in this database platform", declared.name)));
+        }
+        // the declared query can refer to the queries declared before it
+        let mut query = declared.query.clone();
+        for previous in &parent_query.declared_query {
+            if previous.name == declared.name {
+                break;
+            }
+            query.declared_query.push(previous.clone());
+        }
+        w.append("(");
+        try!(self.build_subquery(w, &query));
+        w.append(")");
+        Ok(())
+    }
+
+    fn build_filter(&self, w: &mut SqlFrag, parent_query: &Query, filter: &Filter) -> Result<(), QueryError> {
         if !filter.sub_filters.is_empty() {
             w.append("( ");
         }
-        self.build_condition(w, parent_query, &filter.condition);
+        try!(self.build_condition(w, parent_query, &filter.condition));
 		w.sp();	
         for filt in &filter.sub_filters {
             match filt.connector {
@@ -418,16 +975,17 @@ pub trait Database {
                     w.append("OR ");
                 }
             }
-            self.build_filter(w, parent_query, filt);// build sub filters as well
+            try!(self.build_filter(w, parent_query, filt));// build sub filters as well
         }
         if !filter.sub_filters.is_empty() {
             w.append(" )");
         }
+        Ok(())
     }
 
     /// build the filter clause or the where clause of the query
     /// TODO: add the sub filters
-    fn build_filters(&self, w: &mut SqlFrag, parent_query: &Query, filters: &[Filter]) {
+    fn build_filters(&self, w: &mut SqlFrag, parent_query: &Query, filters: &[Filter]) -> Result<(), QueryError> {
         let mut do_and = false;
         for filter in filters {
             if do_and {
@@ -435,15 +993,16 @@ pub trait Database {
             } else {
                 do_and = true;
             }
-            self.build_filter(w, parent_query, filter);
+            try!(self.build_filter(w, parent_query, filter));
         }
+        Ok(())
     }
 
     /// build the enumerated, distinct, *, columns
     fn build_enumerated_fields(&self,
                                w: &mut SqlFrag,
                                parent_query: &Query,
-                               enumerated_fields: &[Field]) {
+                               enumerated_fields: &[Field]) -> Result<(), QueryError> {
         let mut do_comma = false;
         let mut cnt = 0;
         for field in enumerated_fields {
@@ -456,23 +1015,173 @@ pub trait Database {
             if cnt % 4 == 0 {//break at every 4 columns to encourage sql tuning/revising
                 w.left_river("");
             }
-            self.build_field(w, parent_query, field);
+            try!(self.build_field(w, parent_query, field));
+        }
+        Ok(())
+    }
+
+    /// build the DISTINCT or DISTINCT ON (columns) part of the select statement
+    /// DISTINCT ON is only available in databases that supports it, such as postgresql
+    fn build_distinct(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        if !query.distinct_on_columns.is_empty() {
+            if !self.sql_options().contains(&SqlOption::SupportsDistinctOn) {
+                return Err(QueryError::UnsupportedFeature(
+                    "DISTINCT ON is not supported in this database platform, use DISTINCT or GROUP BY instead".to_owned()));
+            }
+            w.append("DISTINCT ON (");
+            let mut do_comma = false;
+            for column in &query.distinct_on_columns {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                w.append(column);
+            }
+            w.append(") ");
+        } else if query.distinct {
+            w.append("DISTINCT ");
+        }
+        Ok(())
+    }
+
+    /// `OR IGNORE` or `OR REPLACE` for databases that resolves conflicts with any unique constraints
+    /// this is used when no conflict target is specified
+    fn conflict_resolution(&self, query: &Query) -> Option<&'static str> {
+        match query.on_conflict {
+            Some(ref on_conflict) if on_conflict.columns.is_empty()
+                && self.sql_options().contains(&SqlOption::SupportsInsertOrReplace) => {
+                // OR REPLACE deletes the existing record instead of updating the columns,
+                // DO UPDATE without the conflict columns is an error in build_on_conflict
+                match on_conflict.action {
+                    ConflictAction::DoNothing => Some("OR IGNORE"),
+                    ConflictAction::DoUpdate(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// build the ON CONFLICT clause in postgresql and sqlite,
+    /// or ON DUPLICATE KEY UPDATE in mysql.
+    /// Updating when all the inserted columns are conflict columns does nothing
+    fn build_on_conflict(&self, w: &mut SqlFrag, query: &Query, on_conflict: &OnConflict) -> Result<(), QueryError> {
+        let update_columns = on_conflict.update_columns(query);
+        let do_nothing = match on_conflict.action {
+            ConflictAction::DoNothing => true,
+            ConflictAction::DoUpdate(_) => update_columns.is_empty(),
+        };
+        if self.sql_options().contains(&SqlOption::SupportsOnConflict) {
+            w.left_river("ON CONFLICT");
+            if !on_conflict.columns.is_empty() {
+                w.append("(");
+                w.append(&on_conflict.columns.join(", "));
+                w.append(") ");
+            }
+            if let ConflictAction::DoUpdate(_) = on_conflict.action {
+                if on_conflict.columns.is_empty() {
+                    return Err(QueryError::NoColumnSpecified(
+                        "ON CONFLICT DO UPDATE needs the conflict columns".to_owned()));
+                }
+            }
+            if do_nothing {
+                w.left_river("DO");
+                w.append("NOTHING ");
+            } else {
+                w.left_river("DO");
+                w.append("UPDATE SET ");
+                let mut do_comma = false;
+                for column in &update_columns {
+                    if do_comma {
+                        w.commasp();
+                    } else {
+                        do_comma = true;
+                    }
+                    w.append(&format!("{} = EXCLUDED.{}", column, column));
+                }
+                w.sp();
+            }
+        } else if self.sql_options().contains(&SqlOption::SupportsOnDuplicateKey) {
+            w.left_river("ON DUPLICATE KEY UPDATE");
+            if do_nothing {
+                // assigning a column to itself leaves the existing record unchanged
+                match query.get_enumerated_columns().first() {
+                    Some(column) => w.append(&format!("{} = {} ", column.column, column.column)),
+                    None => return Err(QueryError::NoColumnSpecified("There should be columns to insert".to_owned())),
+                };
+            } else {
+                let mut do_comma = false;
+                for column in &update_columns {
+                    if do_comma {
+                        w.commasp();
+                    } else {
+                        do_comma = true;
+                    }
+                    w.append(&format!("{} = VALUES({})", column, column));
+                }
+                w.sp();
+            }
+        } else {
+            return Err(QueryError::UnsupportedFeature("Upsert is not supported in this database platform".to_owned()));
         }
+        Ok(())
     }
 
     /// build the select statment from the query object
-    fn build_select(&self, query: &Query, build_mode: BuildMode) -> SqlFrag {
+    fn build_select(&self, query: &Query, build_mode: BuildMode) -> Result<SqlFrag, QueryError> {
         let mut w = SqlFrag::new(self.sql_options(), build_mode);
+        try!(self.build_select_into(&mut w, query));
+        Ok(w)
+    }
+
+    /// write the select statement into an existing sql fragment
+    fn build_select_into(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        try!(self.build_with(w, query));
+        try!(self.build_select_body(w, query));
+        for &(ref operation, ref combined) in &query.combined {
+            if combined.sql_type != SqlType::SELECT {
+                return Err(QueryError::InvalidQuery("Only select queries can be combined".to_owned()));
+            }
+            if !combined.order_by.is_empty() || combined.range != Range::new() {
+                return Err(QueryError::InvalidQuery(
+                    "ORDER BY, LIMIT and OFFSET applies to the combined result, set it on the first query instead".to_owned()));
+            }
+            let keyword = match *operation {
+                SetOperation::UNION => "UNION",
+                SetOperation::UNION_ALL => "UNION ALL",
+                SetOperation::INTERSECT => "INTERSECT",
+                SetOperation::EXCEPT => "EXCEPT",
+            };
+            if *operation == SetOperation::INTERSECT || *operation == SetOperation::EXCEPT {
+                if !self.sql_options().contains(&SqlOption::SupportsIntersectExcept) {
+                    return Err(QueryError::UnsupportedFeature(
+                        "INTERSECT and EXCEPT are not supported in this database platform".to_owned()));
+                }
+            }
+            w.left_river(keyword);
+            let inherited = self.inherit_declared_query(query, combined);
+            try!(self.build_select_body(w, inherited.as_ref().unwrap_or(combined)));
+        }
+        try!(self.build_order_by(w, query));
+        self.build_range(w, query);
+        Ok(())
+    }
+
+    /// build the select statement without the ORDER BY, LIMIT and OFFSET
+    /// which applies to the whole result when the query is combined with other queries
+    fn build_select_body(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
         w.left_river("SELECT");
-        self.build_enumerated_fields(&mut w, query, &query.enumerated_fields); //TODO: add support for column_sql, fields, functions
+        try!(self.build_distinct(w, query));
+        try!(self.build_enumerated_fields(w, query, &query.enumerated_fields)); //TODO: add support for column_sql, fields, functions
         w.left_river("FROM");
 
-        assert!(!query.from.is_empty(),
-                "There should be table, query, function to select from");
+        if query.from.is_empty() {
+            return Err(QueryError::NoTableSpecified("There should be table, query, function to select from".to_owned()));
+        }
 		let mut do_comma = false;
 		for field in &query.from{
 			if do_comma {w.commasp();}else{do_comma = true;}
-			self.build_source_field(&mut w, query, field);
+			try!(self.build_source_field(w, query, field));
 		}
         if !query.joins.is_empty() {
             for join in &query.joins {
@@ -498,15 +1207,24 @@ pub trait Database {
                     None => (),
                 }
                 w.append("JOIN ");
-                w.append(&join.table_name.complete_name());
+                match self.get_inline_declared_query(query, &join.table_name) {
+                    Some(declared) => {
+                        try!(self.build_inline_declared_query(w, query, declared));
+                        w.append(" AS ");
+                        w.append(&declared.name);
+                    }
+                    None => {
+                        w.append(&join.table_name.complete_name());
+                    }
+                }
 				w.right_river("ON ");
-				self.build_filter(&mut w, query, &join.on);
+				try!(self.build_filter(w, query, &join.on));
             }
         }
 
         if !query.filters.is_empty() {
             w.left_river("WHERE ");
-            self.build_filters(&mut w, query, &query.filters);
+            try!(self.build_filters(w, query, &query.filters));
         }
 
         if !query.group_by.is_empty() {
@@ -518,7 +1236,7 @@ pub trait Database {
                 } else {
                     do_comma = true;
                 }
-                self.build_operand(&mut w, query, operand);
+                try!(self.build_operand(w, query, operand));
                 w.append(" ");
             }
         }
@@ -532,10 +1250,13 @@ pub trait Database {
                 } else {
                     do_comma = true;
                 }
-                self.build_filter(&mut w, query, hav);
+                try!(self.build_filter(w, query, hav));
             }
         }
+        Ok(())
+    }
 
+    fn build_order_by(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
         if !query.order_by.is_empty() {
             w.left_river("ORDER BY ");
             let mut do_comma = false;
@@ -545,27 +1266,37 @@ pub trait Database {
                 } else {
                     do_comma = true;
                 }
-				self.build_operand(&mut w, query, &order.operand);
-                match &order.direction {
-					&Some(ref direction) => {
-						match direction{
-                    		&Direction::ASC => w.append(" ASC"),
-                    		&Direction::DESC => w.append(" DESC"),
-						}
-					},
-					&None => w.append("")
-                };
-                match &order.nulls_where {
-					&Some(ref nulls_where) => {
-						match nulls_where{
-                    		&NullsWhere::FIRST => w.append(" NULLS FIRST"),
-                    		&NullsWhere::LAST => w.append(" NULLS LAST"),
-						}
-					},
-					&None => w.append(""),
-                };
+                try!(self.build_order(w, query, order));
             }
         }
+        Ok(())
+    }
+
+    /// build the operand with its direction, and where the nulls are placed
+    fn build_order(&self, w: &mut SqlFrag, query: &Query, order: &Order) -> Result<(), QueryError> {
+        try!(self.build_operand(w, query, &order.operand));
+        match &order.direction {
+            &Some(ref direction) => {
+                match direction{
+                    &Direction::ASC => w.append(" ASC"),
+                    &Direction::DESC => w.append(" DESC"),
+                }
+            },
+            &None => w.append("")
+        };
+        match &order.nulls_where {
+            &Some(ref nulls_where) => {
+                match nulls_where{
+                    &NullsWhere::FIRST => w.append(" NULLS FIRST"),
+                    &NullsWhere::LAST => w.append(" NULLS LAST"),
+                }
+            },
+            &None => w.append(""),
+        };
+        Ok(())
+    }
+
+    fn build_range(&self, w: &mut SqlFrag, query: &Query) {
         match query.range.limit{
             Some(limit) => {
                 w.left_river("LIMIT ");
@@ -580,45 +1311,80 @@ pub trait Database {
             },
             None => (),
         }
-        w
     }
 
-    /// TODO: when the number of values is greater than the number of columns
-	/// wrap it into another set and make sure the values are in multiples of the the n columns
+    /// when the number of values is greater than the number of columns
+    /// the values are wrapped into multiple row sets of n columns each
 	/// http://www.postgresql.org/docs/9.0/static/dml-insert.html
-    fn build_insert(&self, query: &Query, build_mode: BuildMode) -> SqlFrag {
+    fn build_insert(&self, query: &Query, build_mode: BuildMode) -> Result<SqlFrag, QueryError> {
         let mut w = SqlFrag::new(self.sql_options(), build_mode);
+        try!(self.build_insert_into(&mut w, query));
+        Ok(w)
+    }
+
+    fn build_insert_into(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        try!(self.build_with(w, query));
         w.left_river("INSERT");
+        if let Some(resolution) = self.conflict_resolution(query) {
+            w.append(resolution);
+            w.sp();
+        }
         w.append("INTO ");
-        let into_table = query.get_from_table();
-        assert!(into_table.is_some(), "There should be table to insert to");
-        if let Some(table_name) = into_table {
-            if self.sql_options().contains(&SqlOption::UsesSchema) {
-                w.append(&table_name.complete_name());
-            } else {
-                w.append(&table_name.name);
+        match query.get_from_table() {
+            Some(table_name) => {
+                if self.sql_options().contains(&SqlOption::UsesSchema) {
+                    w.append(&table_name.complete_name());
+                } else {
+                    w.append(&table_name.name);
+                }
             }
+            None => return Err(QueryError::NoTableSpecified("There should be table to insert to".to_owned())),
         }
 
 
         w.append("( ");
-        self.build_enumerated_fields(&mut w, query, &query.enumerated_fields); //TODO: add support for column_sql, fields, functions
+        try!(self.build_enumerated_fields(w, query, &query.enumerated_fields)); //TODO: add support for column_sql, fields, functions
         w.append(" ) ");
-        assert!(!query.values.is_empty(),
-                "values should not be empty, when inserting records");
+        let columns = query.enumerated_fields.len();
+        if columns == 0 {
+            return Err(QueryError::NoColumnSpecified("There should be columns to insert".to_owned()));
+        }
+        if query.values.is_empty() {
+            return Err(QueryError::ValuesMismatch("values should not be empty, when inserting records".to_owned()));
+        }
+        if query.values.len() % columns != 0 {
+            return Err(QueryError::ValuesMismatch(
+                format!("{} values can not be inserted into {} columns, the number of values should be a multiple of the number of columns",
+                        query.values.len(), columns)));
+        }
         if !query.values.is_empty() {
             w.left_river("VALUES");
-            w.append("(");
-            let mut do_comma = false;
-            for vo in &query.values {
-                if do_comma {
-                    w.commasp();
+            let mut do_row_comma = false;
+            for row in query.values.chunks(columns) {
+                if do_row_comma {
+                    w.comma();
+                    w.right_river("");
                 } else {
-                    do_comma = true;
+                    do_row_comma = true;
+                }
+                w.append("(");
+                let mut do_comma = false;
+                for vo in row {
+                    if do_comma {
+                        w.commasp();
+                    } else {
+                        do_comma = true;
+                    }
+                    try!(self.build_operand(w, query, vo));
                 }
-                self.build_operand(&mut w, query, vo);
+                w.append(")");
+            }
+            w.append(" ");
+        }
+        if self.conflict_resolution(query).is_none() {
+            if let Some(ref on_conflict) = query.on_conflict {
+                try!(self.build_on_conflict(w, query, on_conflict));
             }
-            w.append(") ");
         }
         if !query.enumerated_returns.is_empty() {
             if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
@@ -630,24 +1396,33 @@ pub trait Database {
                     } else {
                         do_comma = true;
                     }
-                    self.build_field(&mut w, query, field);
+                    try!(self.build_field(w, query, field));
                 }
             }
         }
         w.ln();
-        w
+        Ok(())
     }
 
 
-    fn build_update(&self, query: &Query, build_mode: BuildMode) -> SqlFrag {
+    fn build_update(&self, query: &Query, build_mode: BuildMode) -> Result<SqlFrag, QueryError> {
         let mut w = SqlFrag::new(self.sql_options(), build_mode);
+        try!(self.build_update_into(&mut w, query));
+        Ok(w)
+    }
+
+    fn build_update_into(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        try!(self.build_with(w, query));
         w.left_river("UPDATE ");
-        let from_table = query.get_from_table();
-        assert!(from_table.is_some(), "There should be table to update from");
-        if let Some(ref from) = from_table {
-            w.append(&from.complete_name());
-        }
+        match query.get_from_table() {
+            Some(from) => w.append(&from.complete_name()),
+            None => return Err(QueryError::NoTableSpecified("There should be table to update from".to_owned())),
+        };
         let enumerated_columns = query.get_enumerated_columns();
+        if enumerated_columns.len() != query.values.len() {
+            return Err(QueryError::ValuesMismatch(
+                format!("{} values can not be set to {} columns", query.values.len(), enumerated_columns.len())));
+        }
         let mut do_comma = false;
         if !enumerated_columns.is_empty() {
             w.left_river("SET ");
@@ -661,16 +1436,13 @@ pub trait Database {
             }
             w.append(&ec.column);
             w.append(" = ");
-            let value = &query.values[column_index];
-            if let &Operand::Value(ref value) = value {
-                w.parameter(value.clone());
-            }
+            try!(self.build_operand(w, query, &query.values[column_index]));
             column_index += 1;
         }
 
         if !query.filters.is_empty() {
             w.left_river("WHERE ");
-            self.build_filters(&mut w, query, &query.filters);
+            try!(self.build_filters(w, query, &query.filters));
         }
         if !query.enumerated_returns.is_empty() {
            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
@@ -682,63 +1454,233 @@ pub trait Database {
                    } else {
                        do_comma = true;
                    }
-                   self.build_field(&mut w, query, field);
+                   try!(self.build_field(w, query, field));
                }
            }
         }
-        w
+        Ok(())
     }
 
-    fn build_delete(&self, query: &Query, build_mode: BuildMode) -> SqlFrag {
+    fn build_delete(&self, query: &Query, build_mode: BuildMode) -> Result<SqlFrag, QueryError> {
         let mut w = SqlFrag::new(self.sql_options(), build_mode);
+        try!(self.build_delete_into(&mut w, query));
+        Ok(w)
+    }
+
+    fn build_delete_into(&self, w: &mut SqlFrag, query: &Query) -> Result<(), QueryError> {
+        try!(self.build_with(w, query));
         w.left_river("DELETE FROM ");
-        let from_table = query.get_from_table();
-        assert!(from_table.is_some(), "There should be table to delete from");
-        if let Some(ref from) = from_table {
-            w.append(&from.complete_name());
-        }
+        match query.get_from_table() {
+            Some(from) => w.append(&from.complete_name()),
+            None => return Err(QueryError::NoTableSpecified("There should be table to delete from".to_owned())),
+        };
         if !query.filters.is_empty() {
             w.left_river("WHERE ");
-            self.build_filters(&mut w, query, &query.filters);
+            try!(self.build_filters(w, query, &query.filters));
         }
-        w
+        Ok(())
     }
 
     fn sql_options(&self) -> Vec<SqlOption>;
 
+    /// the maximum number of parameters the database accepts in a single statement
+    fn max_parameters(&self) -> usize;
+
 }
 
 
 /// Deployment Database should implement this trait,
 /// to enable automated installation of the app, regardless what database platform
 /// the app is developed from.
+///
+/// Platforms which can not alter a table in place (sqlite) build the changes of columns and constraints
+/// as a rebuild of the table, which drops the table. Execute that sql outside of a transaction
+/// with the foreign keys switched off, dropping the table deletes the records referring to it otherwise.
 pub trait DatabaseDDL{
     //////////////////////////////////////////
     /// The following methods involves DDL(Data definition language) operation
     // //////////////////////////////////////
     /// create a database schema
-    fn create_schema(&self, schema: &str);
+    fn create_schema(&self, schema: &str, if_not_exists: bool) -> Result<(), DbError>;
+
+    /// build sql for create schema
+    fn build_create_schema(&self, schema: &str, if_not_exists: bool) -> SqlFrag;
 
     /// drop the database schema
-    fn drop_schema(&self, schema: &str);
+    fn drop_schema(&self, schema: &str, if_exists: bool) -> Result<(), DbError>;
+
+    /// build sql for drop schema
+    fn build_drop_schema(&self, schema: &str, if_exists: bool) -> SqlFrag;
 
     /// create a database table based on the Model Definition
-    fn create_table(&self, model: &Table);
+    fn create_table(&self, model: &Table, if_not_exists: bool) -> Result<(), DbError>;
 
-    /// build sql for create table
-    fn build_create_table(&self, table: &Table) -> SqlFrag;
+    /// build sql for create table, including the column constraints and comments
+    fn build_create_table(&self, table: &Table, if_not_exists: bool) -> SqlFrag;
 
     /// rename table, in the same schema
-    fn rename_table(&self, table: &Table, new_tablename: String);
+    fn rename_table(&self, table: &Table, new_tablename: String) -> Result<(), DbError>;
+
+    /// build sql for rename table
+    fn build_rename_table(&self, table: &Table, new_tablename: &str) -> SqlFrag;
 
     /// drop table
-    fn drop_table(&self, table: &Table);
+    fn drop_table(&self, table: &Table, if_exists: bool) -> Result<(), DbError>;
+
+    /// build sql for drop table
+    fn build_drop_table(&self, table: &Table, if_exists: bool) -> SqlFrag;
 
     /// set the foreign key constraint of a table
-    fn set_foreign_constraint(&self, model: &Table);
+    fn set_foreign_constraint(&self, model: &Table) -> Result<(), DbError>;
+
+    /// build sql adding the foreign key constraints of the table
+    fn build_foreign_constraint(&self, model: &Table) -> SqlFrag;
 
     /// set the primary key constraint of a table
-    fn set_primary_constraint(&self, model: &Table);
+    fn set_primary_constraint(&self, model: &Table) -> Result<(), DbError>;
+
+    /// build sql adding the primary key constraint of the table
+    fn build_primary_constraint(&self, model: &Table) -> SqlFrag;
+
+    /// build sql adding the column to the table
+    fn build_add_column(&self, table: &Table, column: &Column) -> SqlFrag;
+
+    /// build sql dropping the column of the table
+    fn build_drop_column(&self, table: &Table, column: &Column) -> SqlFrag;
+
+    /// build sql changing the type, nullability and default of the column to its definition
+    fn build_alter_column(&self, table: &Table, column: &Column) -> SqlFrag;
+
+    /// build sql adding the foreign key constraint of this column
+    fn build_add_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag;
+
+    /// build sql dropping the foreign key constraint of this column
+    fn build_drop_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag;
+
+    /// build sql dropping the primary key constraint of the table
+    fn build_drop_primary_constraint(&self, table: &Table) -> SqlFrag;
+}
+
+/// split an sql script into its statements on the `;` which are not inside
+/// a quoted string, a quoted identifier or a comment
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                current.push(c);
+                while let Some(quoted) = chars.next() {
+                    current.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                while let Some(commented) = chars.next() {
+                    if commented == '\n' {
+                        current.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                while let Some(commented) = chars.next() {
+                    if previous == '*' && commented == '/' {
+                        break;
+                    }
+                    previous = commented;
+                }
+            }
+            ';' => {
+                if !current.trim().is_empty() {
+                    statements.push(current.trim().to_owned());
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        statements.push(current.trim().to_owned());
+    }
+    statements
+}
+
+/// run the statements in a transaction of their own, unless there is already an active transaction
+/// or the transaction is not needed, the transaction is rolled back when the statements fail
+fn all_or_nothing<D, T, F>(db: &D, needed: bool, statements: F) -> Result<T, DbError>
+    where D: Database + ?Sized,
+          F: FnOnce() -> Result<T, DbError>
+{
+    if !needed || db.is_transacted() {
+        return statements();
+    }
+    try!(db.begin());
+    match statements() {
+        Ok(result) => {
+            try!(db.commit());
+            Ok(result)
+        }
+        Err(e) => {
+            if let Err(rollback) = db.rollback() {
+                warn!("Unable to rollback transaction: {}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// quote the string as an sql string literal
+pub fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace("'", "''"))
+}
+
+/// the sql of the default value of a column, written inline since DDL does not accept parameters.
+/// String values are the default expressions as extracted from the database meta data,
+/// ie: `now()`, `'pending'::text`, and are used verbatim
+pub fn default_sql(operand: &Operand) -> Option<String> {
+    match *operand {
+        Operand::Value(ref value) => {
+            match *value {
+                Value::String(ref expression) => Some(expression.to_owned()),
+                Value::Bool(x) => Some(if x { "TRUE".to_owned() } else { "FALSE".to_owned() }),
+                Value::I8(x) => Some(x.to_string()),
+                Value::I16(x) => Some(x.to_string()),
+                Value::I32(x) => Some(x.to_string()),
+                Value::I64(x) => Some(x.to_string()),
+                Value::U8(x) => Some(x.to_string()),
+                Value::U16(x) => Some(x.to_string()),
+                Value::U32(x) => Some(x.to_string()),
+                Value::U64(x) => Some(x.to_string()),
+                Value::F32(x) => Some(x.to_string()),
+                Value::F64(x) => Some(x.to_string()),
+                Value::Uuid(ref x) => Some(quote_literal(&x.hyphenated().to_string())),
+                Value::DateTime(ref x) => Some(quote_literal(&x.to_rfc3339())),
+                Value::NaiveDate(ref x) => Some(quote_literal(&x.to_string())),
+                Value::NaiveTime(ref x) => Some(quote_literal(&x.to_string())),
+                Value::NaiveDateTime(ref x) => Some(quote_literal(&x.to_string())),
+                Value::Json(ref x) => Some(quote_literal(&x.to_string())),
+                Value::None(_) => Some("NULL".to_owned()),
+                Value::VecU8(_) | Value::Object(_) => None,
+            }
+        }
+        Operand::QuerySource(QuerySource::Function(ref function)) => {
+            let mut params = vec![];
+            for param in &function.params {
+                match default_sql(param) {
+                    Some(param) => params.push(param),
+                    None => return None,
+                }
+            }
+            Some(format!("{}({})", function.function, params.join(", ")))
+        }
+        _ => None,
+    }
 }
 
 
@@ -770,6 +1712,12 @@ pub trait DatabaseDev {
     /// returns (module, type)
     fn dbtype_to_rust_type(&self, db_type: &str) -> (Vec<String>, Type);
 
+    /// whether `dbtype_to_rust_type` has an equivalent for this type
+    /// rather than falling back to String
+    fn has_rust_type(&self, _db_type: &str) -> bool {
+        true
+    }
+
     fn rust_type_to_dbtype(&self, rust_type: &Type) -> String;
 
 }
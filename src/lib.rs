@@ -15,7 +15,7 @@
 //! use rustc_serialize::json;
 //! use rustorm::query::Query;
 //! use rustorm::query::{Filter,Equality};
-//! use rustorm::dao::{Dao,IsDao};
+//! use rustorm::dao::{Dao,IsDao,DaoError};
 //! use rustorm::pool::ManagedPool;
 //! use rustorm::table::{IsTable,Table};
 //! use rustorm::query::builder::SELECT_ALL;
@@ -27,12 +27,12 @@
 //!     pub description:Option<String>,
 //! }
 //! impl IsDao for Product{
-//!     fn from_dao(dao:&Dao)->Self{
-//!         Product{
-//!             product_id: dao.get("product_id"),
-//!             name: dao.get_opt("name"),
-//!             description: dao.get_opt("description"),
-//!         }
+//!     fn try_from_dao(dao:&Dao)->Result<Self, DaoError>{
+//!         Ok(Product{
+//!             product_id: try!(dao.try_get("product_id")),
+//!             name: try!(dao.try_get_opt("name")),
+//!             description: try!(dao.try_get_opt("description")),
+//!         })
 //!     }
 //!    fn to_dao(&self)->Dao{
 //!        let mut dao = Dao::new();
@@ -93,6 +93,8 @@ extern crate r2d2_postgres;
 #[cfg(feature = "sqlite")]
 extern crate r2d2_sqlite;
 extern crate time;
+#[macro_use]
+extern crate log;
 
 
 //pub mod em;
@@ -104,3 +106,7 @@ pub mod table;
 pub mod writer;
 pub mod config;
 pub mod pool;
+pub mod transaction;
+pub mod schema;
+pub mod migration;
+pub mod transfer;
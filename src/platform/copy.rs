@@ -0,0 +1,498 @@
+//! Encoding of rows in the formats of the postgresql COPY statement,
+//! see http://www.postgresql.org/docs/9.4/static/sql-copy.html#AEN71916
+use std::io::{self, Read};
+use std::iter::{self, Peekable};
+use std::collections::BTreeMap;
+use chrono::naive::date::NaiveDate;
+use chrono::naive::time::NaiveTime;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::datetime::DateTime;
+use chrono::offset::utc::UTC;
+use rustc_serialize::json::Json;
+use uuid::Uuid;
+use dao::{Dao, Value, Type, FromValue};
+use table::Table;
+
+
+/// the format of the data sent or received with COPY
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+pub enum CopyFormat {
+    /// tab separated values, one row per line, NULL is written as `\N`
+    Text,
+    /// the binary representation of each type, faster to parse on the server
+    /// but the values must match the column types exactly
+    Binary,
+}
+
+impl CopyFormat {
+    /// the options of the COPY statement for this format
+    pub fn options(&self) -> &'static str {
+        match *self {
+            CopyFormat::Text => "",
+            CopyFormat::Binary => " WITH (FORMAT binary)",
+        }
+    }
+}
+
+/// a column of the copied rows: name, generic type and database type
+pub type CopyColumn = (String, Type, String);
+
+/// the columns of the table which are present in the first dao,
+/// or the keys of the first dao when the table columns are not known
+pub fn copy_columns(table: &Table, first: Option<&Dao>) -> Vec<CopyColumn> {
+    let first = match first {
+        Some(first) => first,
+        None => return vec![],
+    };
+    if table.columns.is_empty() {
+        first.values
+             .iter()
+             .map(|(key, value)| (key.to_owned(), value.get_type(), String::new()))
+             .collect()
+    } else {
+        table.columns
+             .iter()
+             .filter(|c| first.values.contains_key(&c.name))
+             .map(|c| (c.name.to_owned(), c.data_type.clone(), c.db_data_type.to_owned()))
+             .collect()
+    }
+}
+
+fn epoch_2000() -> NaiveDateTime {
+    NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, 0)
+}
+
+fn hstore_text(map: &BTreeMap<String, Value>) -> String {
+    let escape = |s: &str| s.replace("\\", "\\\\").replace("\"", "\\\"");
+    let pairs: Vec<String> = map.iter()
+                                .map(|(key, value)| {
+                                    match text_value(value) {
+                                        Some(value) => format!("\"{}\"=>\"{}\"", escape(key), escape(&value)),
+                                        None => format!("\"{}\"=>NULL", escape(key)),
+                                    }
+                                })
+                                .collect();
+    pairs.join(", ")
+}
+
+/// the text representation of the value as accepted by the input functions of postgresql,
+/// None for NULL
+pub fn text_value(value: &Value) -> Option<String> {
+    let text = match *value {
+        Value::Bool(x) => if x { "t".to_owned() } else { "f".to_owned() },
+        Value::I8(x) => x.to_string(),
+        Value::I16(x) => x.to_string(),
+        Value::I32(x) => x.to_string(),
+        Value::I64(x) => x.to_string(),
+        Value::U8(x) => x.to_string(),
+        Value::U16(x) => x.to_string(),
+        Value::U32(x) => x.to_string(),
+        Value::U64(x) => x.to_string(),
+        Value::F32(x) => float_text(x as f64),
+        Value::F64(x) => float_text(x),
+        Value::String(ref x) => x.to_owned(),
+        Value::VecU8(ref x) => {
+            let hex: Vec<String> = x.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\\x{}", hex.concat())
+        }
+        Value::Json(ref x) => x.to_string(),
+        Value::Uuid(ref x) => x.hyphenated().to_string(),
+        Value::DateTime(ref x) => x.to_rfc3339(),
+        Value::NaiveDateTime(ref x) => format!("{} {}", x.date(), x.time()),
+        Value::NaiveDate(ref x) => x.to_string(),
+        Value::NaiveTime(ref x) => x.to_string(),
+        Value::Object(ref x) => hstore_text(x),
+        Value::None(_) => return None,
+    };
+    Some(text)
+}
+
+fn float_text(x: f64) -> String {
+    if x.is_nan() {
+        "NaN".to_owned()
+    } else if x.is_infinite() {
+        if x > 0.0 { "Infinity".to_owned() } else { "-Infinity".to_owned() }
+    } else {
+        x.to_string()
+    }
+}
+
+/// escape the backslash and the delimiters of the text format
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// a row in the text format, including the line terminator
+pub fn text_row(dao: &Dao, columns: &[CopyColumn]) -> String {
+    let fields: Vec<String> = columns.iter()
+                                     .map(|&(ref name, _, _)| {
+                                         match dao.values.get(name).and_then(text_value) {
+                                             Some(text) => escape_text(&text),
+                                             None => "\\N".to_owned(),
+                                         }
+                                     })
+                                     .collect();
+    format!("{}\n", fields.join("\t"))
+}
+
+fn push_i16(buffer: &mut Vec<u8>, x: i16) {
+    buffer.push((x >> 8) as u8);
+    buffer.push(x as u8);
+}
+
+fn push_i32(buffer: &mut Vec<u8>, x: i32) {
+    for shift in &[24, 16, 8, 0] {
+        buffer.push((x >> *shift) as u8);
+    }
+}
+
+fn push_i64(buffer: &mut Vec<u8>, x: i64) {
+    for shift in &[56, 48, 40, 32, 24, 16, 8, 0] {
+        buffer.push((x >> *shift) as u8);
+    }
+}
+
+fn convert<T: FromValue>(value: Value) -> Result<T, String> {
+    T::try_from_type(value).map_err(|e| format!("{}", e))
+}
+
+fn micros(duration_micros: Option<i64>) -> Result<i64, String> {
+    duration_micros.ok_or("timestamp out of range".to_owned())
+}
+
+/// the numeric in its binary representation, parsed from the decimal text:
+/// the number of base 10000 digits, the weight of the first digit, the sign,
+/// the number of decimal digits after the point, followed by the digits
+pub fn numeric_binary(text: &str) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![];
+    let text = text.trim();
+    if text == "NaN" {
+        push_i16(&mut buffer, 0);
+        push_i16(&mut buffer, 0);
+        push_i16(&mut buffer, 0xC000u16 as i16);
+        push_i16(&mut buffer, 0);
+        return Ok(buffer);
+    }
+    let (negative, unsigned) = if text.starts_with('-') {
+        (true, &text[1..])
+    } else {
+        (false, text.trim_left_matches('+'))
+    };
+    let mut parts = unsigned.splitn(2, '.');
+    let integer = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    if (integer.is_empty() && fraction.is_empty()) ||
+       !integer.chars().chain(fraction.chars()).all(|c| c.is_digit(10)) {
+        return Err(format!("{} is not a numeric", text));
+    }
+    // the integer part is padded on the left and the fraction on the right to whole base 10000 digits
+    let integer_padding = (4 - integer.len() % 4) % 4;
+    let fraction_padding = (4 - fraction.len() % 4) % 4;
+    let zeros = |n: usize| iter::repeat('0').take(n).collect::<String>();
+    let padded = format!("{}{}{}{}", zeros(integer_padding), integer, fraction, zeros(fraction_padding));
+    let mut digits: Vec<i16> = padded.as_bytes()
+                                     .chunks(4)
+                                     .map(|chunk| chunk.iter().fold(0i16, |n, d| n * 10 + (d - b'0') as i16))
+                                     .collect();
+    let mut weight = ((integer_padding + integer.len()) / 4) as i16 - 1;
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        weight -= 1;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    if digits.is_empty() {
+        weight = 0;
+    }
+    push_i16(&mut buffer, digits.len() as i16);
+    push_i16(&mut buffer, weight);
+    push_i16(&mut buffer, if negative && !digits.is_empty() { 0x4000 } else { 0 });
+    push_i16(&mut buffer, fraction.len() as i16);
+    for digit in digits {
+        push_i16(&mut buffer, digit);
+    }
+    Ok(buffer)
+}
+
+/// the binary representation of the value for a column of this type, None for NULL
+pub fn binary_value(value: Value, data_type: &Type, db_type: &str) -> Result<Option<Vec<u8>>, String> {
+    if let Value::None(_) = value {
+        return Ok(None);
+    }
+    let mut buffer = vec![];
+    match *data_type {
+        Type::Bool => buffer.push(try!(convert::<bool>(value)) as u8),
+        Type::I8 => buffer.push(try!(convert::<i8>(value)) as u8),
+        Type::I16 => push_i16(&mut buffer, try!(convert(value))),
+        Type::I32 => push_i32(&mut buffer, try!(convert(value))),
+        Type::I64 => push_i64(&mut buffer, try!(convert(value))),
+        Type::U32 => push_i32(&mut buffer, try!(convert::<u32>(value)) as i32),
+        _ if db_type == "numeric" => {
+            let text = text_value(&value).unwrap_or_else(String::new);
+            buffer = try!(numeric_binary(&text));
+        }
+        Type::F32 => {
+            let bits = try!(convert::<f32>(value)).to_bits();
+            push_i32(&mut buffer, bits as i32);
+        }
+        Type::F64 => {
+            let bits = try!(convert::<f64>(value)).to_bits();
+            push_i64(&mut buffer, bits as i64);
+        }
+        Type::String => buffer.extend(try!(convert::<String>(value)).as_bytes()),
+        Type::VecU8 => buffer.extend(&try!(convert::<Vec<u8>>(value))),
+        Type::Json => {
+            // jsonb is prefixed with the version of its binary format
+            if db_type == "jsonb" {
+                buffer.push(1);
+            }
+            let json = match value {
+                Value::String(s) => s,
+                value => try!(convert::<Json>(value)).to_string(),
+            };
+            buffer.extend(json.as_bytes());
+        }
+        Type::Uuid => buffer.extend(try!(convert::<Uuid>(value)).as_bytes()),
+        Type::DateTime => {
+            let timestamp = try!(convert::<DateTime<UTC>>(value)).naive_utc();
+            push_i64(&mut buffer, try!(micros((timestamp - epoch_2000()).num_microseconds())));
+        }
+        Type::NaiveDateTime => {
+            let timestamp = try!(convert::<NaiveDateTime>(value));
+            push_i64(&mut buffer, try!(micros((timestamp - epoch_2000()).num_microseconds())));
+        }
+        Type::NaiveDate => {
+            let date = try!(convert::<NaiveDate>(value));
+            push_i32(&mut buffer, (date - epoch_2000().date()).num_days() as i32);
+        }
+        Type::NaiveTime => {
+            let time = try!(convert::<NaiveTime>(value));
+            push_i64(&mut buffer, try!(micros((time - NaiveTime::from_hms(0, 0, 0)).num_microseconds())));
+        }
+        Type::Object => {
+            let map = match value {
+                Value::Object(map) => map,
+                value => return Err(format!("expected an object, found {:?}", value)),
+            };
+            push_i32(&mut buffer, map.len() as i32);
+            for (key, value) in &map {
+                push_i32(&mut buffer, key.len() as i32);
+                buffer.extend(key.as_bytes());
+                match text_value(value) {
+                    Some(text) => {
+                        push_i32(&mut buffer, text.len() as i32);
+                        buffer.extend(text.as_bytes());
+                    }
+                    None => push_i32(&mut buffer, -1),
+                }
+            }
+        }
+        _ => return Err(format!("{:?} has no postgresql binary encoding", data_type)),
+    }
+    Ok(Some(buffer))
+}
+
+/// the signature, flags and header extension length which start the binary format
+pub fn binary_header() -> Vec<u8> {
+    let mut header = b"PGCOPY\n\xff\r\n\0".to_vec();
+    push_i32(&mut header, 0);
+    push_i32(&mut header, 0);
+    header
+}
+
+/// a row in the binary format, the values are converted to the column types
+pub fn binary_row(dao: &Dao, columns: &[CopyColumn]) -> Result<Vec<u8>, String> {
+    let mut row = vec![];
+    push_i16(&mut row, columns.len() as i16);
+    for &(ref name, ref data_type, ref db_type) in columns {
+        let value = dao.values.get(name).cloned().unwrap_or(Value::None(data_type.clone()));
+        match binary_value(value, data_type, db_type) {
+            Ok(Some(bytes)) => {
+                push_i32(&mut row, bytes.len() as i32);
+                row.extend(bytes);
+            }
+            Ok(None) => push_i32(&mut row, -1),
+            Err(e) => return Err(format!("column {}: {}", name, e)),
+        }
+    }
+    Ok(row)
+}
+
+/// encodes the daos one row at a time as they are read by COPY FROM STDIN,
+/// so the daos are never all in memory
+pub struct CopyReader<I: Iterator<Item = Dao>> {
+    daos: Peekable<I>,
+    columns: Vec<CopyColumn>,
+    format: CopyFormat,
+    buffer: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl <I: Iterator<Item = Dao>>CopyReader<I> {
+    pub fn new(table: &Table, daos: I, format: CopyFormat) -> Self {
+        let mut daos = daos.peekable();
+        let columns = copy_columns(table, daos.peek());
+        let buffer = match format {
+            CopyFormat::Text => vec![],
+            CopyFormat::Binary => binary_header(),
+        };
+        CopyReader {
+            daos: daos,
+            columns: columns,
+            format: format,
+            buffer: buffer,
+            position: 0,
+            finished: false,
+        }
+    }
+
+    /// the columns of the COPY statement
+    pub fn columns(&self) -> Vec<&str> {
+        self.columns.iter().map(|&(ref name, _, _)| &**name).collect()
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        self.buffer.clear();
+        self.position = 0;
+        match self.daos.next() {
+            Some(dao) => {
+                match self.format {
+                    CopyFormat::Text => self.buffer.extend(text_row(&dao, &self.columns).as_bytes()),
+                    CopyFormat::Binary => {
+                        match binary_row(&dao, &self.columns) {
+                            Ok(row) => self.buffer.extend(row),
+                            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+                        }
+                    }
+                }
+            }
+            None => {
+                if self.format == CopyFormat::Binary {
+                    push_i16(&mut self.buffer, -1);
+                }
+                self.finished = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl <I: Iterator<Item = Dao>>Read for CopyReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            try!(self.fill());
+        }
+        let n = ::std::cmp::min(buf.len(), self.buffer.len() - self.position);
+        for (dst, src) in buf.iter_mut().zip(&self.buffer[self.position..self.position + n]) {
+            *dst = *src;
+        }
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// the value as an escape string literal, so backslashes are escaped
+/// regardless of the `standard_conforming_strings` setting
+fn literal(value: &Value) -> String {
+    match text_value(value) {
+        Some(text) => format!("E'{}'", text.replace('\\', "\\\\").replace('\'', "''")),
+        None => "NULL".to_owned(),
+    }
+}
+
+/// the length of the tag of the dollar quoted string starting at `start`, ie: `$$` or `$body$`
+fn dollar_tag_len(chars: &[char], start: usize) -> Option<usize> {
+    let mut end = start + 1;
+    while end < chars.len() && is_identifier_char(chars[end]) {
+        end += 1;
+    }
+    match (chars.get(end), chars.get(start + 1)) {
+        (Some(&'$'), Some(c)) if !c.is_digit(10) => Some(end + 1 - start),
+        _ => None,
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// replace the `$1`, `$2`.. parameters of the sql with the values as quoted literals,
+/// needed by statements which don't accept parameters such as COPY.
+/// Quoted strings, quoted identifiers and dollar quoted strings are left as they are
+pub fn inline_params(sql: &str, params: &[Value]) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut inlined = String::with_capacity(sql.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\'' | '"' => {
+                // backslash escapes the next character in E'..' strings
+                let escaped = c == '\'' && i > 0 && (chars[i - 1] == 'E' || chars[i - 1] == 'e') &&
+                              (i == 1 || !is_identifier_char(chars[i - 2]));
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != c {
+                    if escaped && chars[end] == '\\' {
+                        end += 1;
+                    }
+                    end += 1;
+                }
+                let end = if end < chars.len() { end + 1 } else { chars.len() };
+                inlined.extend(&chars[i..end]);
+                i = end;
+            }
+            '$' if i + 1 < chars.len() && chars[i + 1].is_digit(10) => {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end].is_digit(10) {
+                    end += 1;
+                }
+                let number: String = chars[i + 1..end].iter().cloned().collect();
+                let param = number.parse::<usize>()
+                                  .ok()
+                                  .and_then(|n| if n > 0 { params.get(n - 1) } else { None });
+                match param {
+                    Some(param) => inlined.push_str(&literal(param)),
+                    None => inlined.extend(&chars[i..end]),
+                }
+                i = end;
+            }
+            '$' => {
+                let end = match dollar_tag_len(&chars, i) {
+                    Some(len) => {
+                        let tag = &chars[i..i + len];
+                        match chars[i + len..].windows(len).position(|w| w == tag) {
+                            Some(pos) => i + len + pos + len,
+                            None => chars.len(),
+                        }
+                    }
+                    None => i + 1,
+                };
+                inlined.extend(&chars[i..end]);
+                i = end;
+            }
+            _ => {
+                inlined.push(c);
+                i += 1;
+            }
+        }
+    }
+    inlined
+}
@@ -1,4 +1,5 @@
 pub mod postgres;
+pub mod copy;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 #[cfg(feature = "mysql")]
@@ -18,6 +19,7 @@ use postgres::error::ConnectError as PgConnectError;
 use mysql::error::MyError;
 #[cfg(feature = "sqlite")]
 use rusqlite::SqliteError;
+use database::ErrorKind;
 
 #[derive(Debug)]
 pub enum PlatformError {
@@ -29,6 +31,45 @@ pub enum PlatformError {
     SqliteError(SqliteError),
 }
 
+impl PlatformError {
+
+    /// the database agnostic kind of this error
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            PlatformError::PostgresError(ref err) => postgres::error_kind(err),
+            PlatformError::PostgresConnectError(_) => ErrorKind::ConnectionLost,
+            #[cfg(feature = "mysql")]
+            PlatformError::MySQLError(ref err) => mysql::error_kind(err),
+            #[cfg(feature = "sqlite")]
+            PlatformError::SqliteError(ref err) => sqlite::error_kind(err),
+        }
+    }
+
+    /// the name of the violated constraint, when provided by the database
+    pub fn constraint(&self) -> Option<String> {
+        match *self {
+            PlatformError::PostgresError(ref err) => postgres::error_constraint(err),
+            PlatformError::PostgresConnectError(_) => None,
+            #[cfg(feature = "mysql")]
+            PlatformError::MySQLError(ref err) => mysql::error_constraint(err),
+            #[cfg(feature = "sqlite")]
+            PlatformError::SqliteError(ref err) => sqlite::error_constraint(err),
+        }
+    }
+
+    /// the name of the table involved in the error, when provided by the database
+    pub fn table(&self) -> Option<String> {
+        match *self {
+            PlatformError::PostgresError(ref err) => postgres::error_table(err),
+            PlatformError::PostgresConnectError(_) => None,
+            #[cfg(feature = "mysql")]
+            PlatformError::MySQLError(ref err) => mysql::error_table(err),
+            #[cfg(feature = "sqlite")]
+            PlatformError::SqliteError(ref err) => sqlite::error_table(err),
+        }
+    }
+}
+
 impl Error for PlatformError {
     fn description(&self) -> &str {
         match *self {
@@ -1,5 +1,5 @@
 use query::Query;
-use dao::Dao;
+use dao::{Dao, ColumnInfo, Rows};
 use table::{Table, Column, Foreign};
 
 use dao::Value;
@@ -13,8 +13,8 @@ use mysql::value::FromValue;
 use mysql::value::IntoValue;
 use mysql::error::MyResult;
 use mysql::value::from_row;
-use mysql::conn::Stmt;
-use mysql::conn::pool::MyPool;
+use mysql::conn::{Stmt, QueryResult};
+use mysql::conn::pool::{MyPool, MyPooledConn};
 use chrono::naive::datetime::NaiveDateTime;
 use chrono::datetime::DateTime;
 use chrono::offset::utc::UTC;
@@ -22,20 +22,111 @@ use chrono::offset::utc::UTC;
 use query::Operand;
 
 
-use database::{Database, DatabaseDev, DatabaseDDL, DbError};
+use database::{Database, DatabaseDev, DatabaseDDL, DbError, DaoStream, StreamStatement};
+use database::{default_sql, quote_literal, split_statements};
 use time::Timespec;
 use dao::Type;
+use transaction::savepoint_name;
+use std::cell::{Cell, RefCell};
+use std::thread::LocalKey;
+use mysql::error::MyError;
+use database::ErrorKind;
 
 pub struct Mysql {
     pool: Option<MyPool>,
+    /// the connection pinned for the duration of a transaction,
+    /// otherwise each statement may be executed on a different connection of the pool
+    conn: RefCell<Option<MyPooledConn>>,
+    /// the number of nested transactions currently active on the pinned connection
+    transaction_depth: Cell<usize>,
+    /// the major and minor version of the server, read once when first needed
+    version_number: Cell<Option<(u32, u32)>>,
 }
+/// a statement prepared on a connection of the pool taken for the stream,
+/// with the names and types of its columns
+pub struct MysqlStreamStatement<'a> {
+    stmt: Stmt<'a>,
+    params: Vec<Value>,
+    columns: Vec<(String, ColumnType)>,
+}
+
+impl <'a>StreamStatement for MysqlStreamStatement<'a> {
+    fn rows<'s>(&'s mut self) -> Result<DaoStream<'s>, DbError> {
+        let param = Mysql::from_rust_type_tosql(&self.params);
+        let rows = try!(self.stmt.execute(&param));
+        Ok(Box::new(MysqlStream {
+            rows: rows,
+            columns: &self.columns,
+        }))
+    }
+}
+
+/// the rows of an unbuffered result, read from the connection as the stream advances.
+/// The connection of the statement can not be used for other statements until the stream is dropped
+pub struct MysqlStream<'s> {
+    rows: QueryResult<'s>,
+    columns: &'s [(String, ColumnType)],
+}
+
+impl <'s>Iterator for MysqlStream<'s> {
+    type Item = Result<Dao, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match self.rows.next() {
+            Some(Ok(row)) => row,
+            Some(Err(e)) => return Some(Err(DbError::from(e))),
+            None => return None,
+        };
+        let mut dao = Dao::new();
+        for (index, &(ref column_name, ref column_type)) in self.columns.iter().enumerate() {
+            let rtype = Mysql::from_sql_to_rust_type(&row, index, column_type);
+            dao.set_value(column_name, rtype);
+        }
+        Some(Ok(dao))
+    }
+}
+
 impl Mysql{
     pub fn new() -> Self {
-        Mysql { pool: None }
+        Mysql {
+            pool: None,
+            conn: RefCell::new(None),
+            transaction_depth: Cell::new(0),
+            version_number: Cell::new(None),
+        }
     }
 
     pub fn with_pooled_connection(pool: MyPool) -> Self {
-        Mysql { pool: Some(pool) }
+        Mysql {
+            pool: Some(pool),
+            conn: RefCell::new(None),
+            transaction_depth: Cell::new(0),
+            version_number: Cell::new(None),
+        }
+    }
+
+    /// whether the server is at least this version, ie: 8.0.11 or 10.3.7-MariaDB,
+    /// features are not assumed when there is no connection to check the version
+    fn is_version_at_least(&self, major: u32, minor: u32) -> bool {
+        let version = match self.version_number.get() {
+            Some(version) => version,
+            None => {
+                if self.pool.is_none() {
+                    return false;
+                }
+                let version = match self.version() {
+                    Ok(version) => {
+                        let mut parts = version.split(|c: char| !c.is_digit(10))
+                                               .map(|p| p.parse::<u32>().unwrap_or(0));
+                        (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+                    }
+                    Err(_) => (0, 0),
+                };
+                self.version_number.set(Some(version));
+                version
+            }
+        };
+        version >= (major, minor)
     }
 
     fn from_rust_type_tosql(types: &[Value]) -> Vec<MyValue> {
@@ -224,6 +315,31 @@ impl Mysql{
         }
     }
 
+    /// the generic type the values of a column type are converted to,
+    /// the same as the conversion done in `from_sql_to_rust_type`
+    fn from_column_type_to_rust_type(column_type: &ColumnType) -> Type {
+        match *column_type {
+            ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => Type::F64,
+            ColumnType::MYSQL_TYPE_TINY => Type::I8,
+            ColumnType::MYSQL_TYPE_SHORT => Type::I16,
+            ColumnType::MYSQL_TYPE_LONG | ColumnType::MYSQL_TYPE_LONGLONG => Type::I64,
+            ColumnType::MYSQL_TYPE_INT24 => Type::I32,
+            ColumnType::MYSQL_TYPE_FLOAT => Type::F32,
+            ColumnType::MYSQL_TYPE_DOUBLE => Type::F64,
+            ColumnType::MYSQL_TYPE_TIMESTAMP
+            | ColumnType::MYSQL_TYPE_DATE
+            | ColumnType::MYSQL_TYPE_TIME
+            | ColumnType::MYSQL_TYPE_DATETIME
+            | ColumnType::MYSQL_TYPE_YEAR => Type::DateTime,
+            _ => Type::String,
+        }
+    }
+
+    /// the name of the column type as sent by the server, ie: `var_string` for MYSQL_TYPE_VAR_STRING
+    fn column_type_name(column_type: &ColumnType) -> String {
+        format!("{:?}", column_type).trim_left_matches("MYSQL_TYPE_").to_lowercase()
+    }
+
     ///
     /// convert rust data type names to database data type names
     /// will be used in generating SQL for table creation
@@ -278,6 +394,18 @@ impl Mysql{
             Type::NaiveTime => {
                 "time".to_owned()
             }
+            Type::U8 => {
+                "tinyint unsigned".to_owned()
+            }
+            Type::U16 => {
+                "smallint unsigned".to_owned()
+            }
+            Type::U64 => {
+                "bigint unsigned".to_owned()
+            }
+            Type::Object => {
+                "json".to_owned()
+            }
             _ => panic!("Unable to get the equivalent database data type for {:?}",
                         rust_type),
         }
@@ -386,11 +514,117 @@ impl Mysql{
     }
 
 
+    /// the column name, type, NOT NULL, DEFAULT and COMMENT used in CREATE TABLE, ADD and MODIFY COLUMN.
+    /// String columns used as keys are created as varchar(255),
+    /// since mysql can not index text columns without a prefix length
+    fn column_definition(&self, column: &Column) -> String {
+        let is_key = column.is_primary || column.is_unique || column.foreign.is_some();
+        let data_type = if is_key && column.data_type == Type::String {
+            "varchar(255)".to_owned()
+        } else {
+            self.rust_type_to_dbtype(&column.data_type)
+        };
+        let mut definition = format!("{} {}", column.name, data_type);
+        if column.not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(ref default) = column.default {
+            if let Some(default) = default_sql(default) {
+                definition.push_str(" DEFAULT ");
+                definition.push_str(&default);
+            }
+        }
+        if let Some(ref comment) = column.comment {
+            definition.push_str(" COMMENT ");
+            definition.push_str(&quote_literal(comment));
+        }
+        definition
+    }
+
     fn get_prepared_statement<'a>(&'a self, sql: &'a str) -> MyResult<Stmt> {
         self.pool.as_ref().unwrap().prepare(sql)
     }
 }
 
+/// the kind of error from the MySQL error number
+pub fn error_kind(err: &MyError) -> ErrorKind {
+    match *err {
+        MyError::MySqlError(ref e) => error_info(e.code, &e.message).0,
+        MyError::MyIoError(_) => ErrorKind::ConnectionLost,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// the name of the violated constraint, extracted from the error message
+pub fn error_constraint(err: &MyError) -> Option<String> {
+    match *err {
+        MyError::MySqlError(ref e) => error_info(e.code, &e.message).1,
+        _ => None,
+    }
+}
+
+/// the name of the table involved in the error, extracted from the error message
+pub fn error_table(err: &MyError) -> Option<String> {
+    match *err {
+        MyError::MySqlError(ref e) => error_info(e.code, &e.message).2,
+        _ => None,
+    }
+}
+
+thread_local!(static DUPLICATE_KEY: Regex = Regex::new("for key '([^']+)'").unwrap());
+thread_local!(static FOREIGN_CONSTRAINT: Regex = Regex::new("CONSTRAINT `([^`]+)`").unwrap());
+thread_local!(static FOREIGN_TABLE: Regex = Regex::new("\\((?:`[^`]+`\\.)?`([^`]+)`, CONSTRAINT").unwrap());
+thread_local!(static CHECK_CONSTRAINT: Regex = Regex::new("[Cc]heck constraint '([^']+)'").unwrap());
+thread_local!(static UNDEFINED_TABLE: Regex = Regex::new("Table '(?:[^'.]+\\.)?([^']+)'").unwrap());
+
+/// the first capture of the pattern in the message
+fn capture(pattern: &'static LocalKey<Regex>, message: &str) -> Option<String> {
+    pattern.with(|re| re.captures(message).and_then(|cap| cap.at(1).map(|c| c.to_owned())))
+}
+
+/// the kind, constraint and table of the error,
+/// MySQL only reports the names in the message
+fn error_info(code: u16, message: &str) -> (ErrorKind, Option<String>, Option<String>) {
+    match code {
+        // Duplicate entry 'x' for key 'product.name_unique'
+        1062 | 1586 => {
+            match capture(&DUPLICATE_KEY, message) {
+                Some(key) => {
+                    match key.rfind('.') {
+                        Some(dot) => (ErrorKind::UniqueViolation,
+                                      Some(key[dot + 1..].to_owned()),
+                                      Some(key[..dot].to_owned())),
+                        None => (ErrorKind::UniqueViolation, Some(key), None),
+                    }
+                }
+                None => (ErrorKind::UniqueViolation, None, None),
+            }
+        }
+        // a foreign key constraint fails (`db`.`table`, CONSTRAINT `fk` FOREIGN KEY ..
+        1216 | 1217 | 1451 | 1452 => {
+            (ErrorKind::ForeignKeyViolation,
+             capture(&FOREIGN_CONSTRAINT, message),
+             capture(&FOREIGN_TABLE, message))
+        }
+        1048 | 1364 => (ErrorKind::NotNullViolation, None, None),
+        // Check constraint 'price_positive' is violated.
+        3819 => (ErrorKind::CheckViolation,
+                 capture(&CHECK_CONSTRAINT, message),
+                 None),
+        1205 => (ErrorKind::LockTimeout, None, None),
+        1213 => (ErrorKind::Deadlock, None, None),
+        1053 | 1077 | 2006 | 2013 => (ErrorKind::ConnectionLost, None, None),
+        1064 => (ErrorKind::SyntaxError, None, None),
+        // Table 'bazaar.product' doesn't exist
+        1146 => {
+            let table = capture(&UNDEFINED_TABLE, message);
+            (ErrorKind::UndefinedTable, None, table)
+        }
+        1054 => (ErrorKind::UndefinedColumn, None, None),
+        _ => (ErrorKind::Other, None, None),
+    }
+}
+
 impl Database for Mysql {
     fn version(&self) -> Result<String, DbError> {
         let sql = "SELECT version()";
@@ -401,17 +635,62 @@ impl Database for Mysql {
         }
     }
 
-    fn begin(&self) {
-        unimplemented!()
+    /// a connection is taken out of the pool and is used
+    /// by all the statements until the outermost transaction is finished
+    fn begin(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            let conn = match self.pool {
+                Some(ref pool) => try!(pool.get_conn()),
+                None => return Err(DbError::new("No connection pool")),
+            };
+            *self.conn.borrow_mut() = Some(conn);
+            // the connection is only pinned once the transaction is started
+            if let Err(e) = self.execute_sql("START TRANSACTION", &[]) {
+                *self.conn.borrow_mut() = None;
+                return Err(e);
+            }
+        } else {
+            try!(self.execute_sql(&format!("SAVEPOINT {}", savepoint_name(depth)), &[]));
+        }
+        self.transaction_depth.set(depth + 1);
+        Ok(())
     }
-    fn commit(&self) {
-        unimplemented!()
+    fn commit(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to commit, there is no active transaction"));
+        }
+        // the transaction is still open when the commit fails,
+        // the pinned connection is kept so it can be rolled back
+        if depth == 1 {
+            try!(self.execute_sql("COMMIT", &[]));
+            *self.conn.borrow_mut() = None;
+        } else {
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint_name(depth - 1)), &[]));
+        }
+        self.transaction_depth.set(depth - 1);
+        Ok(())
     }
-    fn rollback(&self) {
-        unimplemented!()
+    fn rollback(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to rollback, there is no active transaction"));
+        }
+        self.transaction_depth.set(depth - 1);
+        if depth == 1 {
+            let result = self.execute_sql("ROLLBACK", &[]);
+            *self.conn.borrow_mut() = None;
+            try!(result);
+        } else {
+            let savepoint = savepoint_name(depth - 1);
+            try!(self.execute_sql(&format!("ROLLBACK TO SAVEPOINT {}", savepoint), &[]));
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint), &[]));
+        }
+        Ok(())
     }
     fn is_transacted(&self) -> bool {
-        false
+        self.transaction_depth.get() > 0
     }
     fn is_closed(&self) -> bool {
         false
@@ -430,9 +709,22 @@ impl Database for Mysql {
 
     /// return this list of options, supported features in the database
     fn sql_options(&self) -> Vec<SqlOption> {
-        vec![
+        let mut options = vec![
             SqlOption::UsesQuestionMark,//mysql uses question mark instead of the numbered params
-        ]
+            SqlOption::SupportsOnDuplicateKey,
+            SqlOption::UsesConcatFunction,
+            SqlOption::SupportsDefaultKeyword,
+        ];
+        // window functions are available since mysql 8.0
+        if self.is_version_at_least(8, 0) {
+            options.push(SqlOption::SupportsWindowFunctions);
+        }
+        options
+    }
+
+    /// prepared statements are limited to an u16 number of placeholders
+    fn max_parameters(&self) -> usize {
+        65535
     }
 
     fn update(&self, _query: &Query) -> Dao {
@@ -442,32 +734,45 @@ impl Database for Mysql {
         unimplemented!()
     }
 
-    fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError> {
+    /// the source table of a column is the table it is selected from, not its alias in the query,
+    /// computed columns have no source table
+    fn execute_sql_with_columns(&self, sql: &str, params: &[Value]) -> Result<Rows, DbError> {
         println!("SQL: \n{}", sql);
         println!("param: {:?}", params);
         assert!(self.pool.is_some());
-        let mut stmt = try!(self.get_prepared_statement(sql));
+        let mut conn = self.conn.borrow_mut();
+        let mut stmt = match *conn {
+            Some(ref mut conn) => try!(conn.prepare(sql)),
+            None => try!(self.get_prepared_statement(sql)),
+        };
         let mut columns = vec![];
-        for col in stmt.columns_ref().unwrap() {
-            let column_name = String::from_utf8(col.name.clone()).unwrap();
-            println!("column type: {:?}", col.column_type);
-            columns.push( (column_name, col.column_type) );
+        let mut column_types = vec![];
+        if let Some(columns_ref) = stmt.columns_ref() {
+            for col in columns_ref {
+                let column_name = String::from_utf8(col.name.clone()).unwrap();
+                let table = String::from_utf8(col.org_table.clone()).unwrap();
+                println!("column type: {:?}", col.column_type);
+                columns.push(ColumnInfo {
+                    name: column_name,
+                    table: if table.is_empty() { None } else { Some(table) },
+                    db_type: Mysql::column_type_name(&col.column_type),
+                    data_type: Mysql::from_column_type_to_rust_type(&col.column_type),
+                });
+                column_types.push(col.column_type);
+            }
         }
-        let mut daos = vec![];
+        let mut result = Rows::new(columns);
         let param = Mysql::from_rust_type_tosql(params);
         let rows = try!(stmt.execute(&param));
         for row in rows {
             let row = try!(row);
-            let mut index = 0;
-            let mut dao = Dao::new();
-            for &(ref column_name, ref column_type) in &columns {
-                let rtype = Mysql::from_sql_to_rust_type(&row, index, column_type);
-                dao.set_value(&column_name, rtype);
-                index += 1;
+            let mut values = vec![];
+            for (index, column_type) in column_types.iter().enumerate() {
+                values.push(Mysql::from_sql_to_rust_type(&row, index, column_type));
             }
-            daos.push(dao);
+            result.push(values);
         }
-        Ok(daos)
+        Ok(result)
     }
 
     fn execute_sql_with_one_return(&self,
@@ -482,6 +787,43 @@ impl Database for Mysql {
         }
     }
 
+    /// the rows are read unbuffered on a connection of the pool taken for the stream.
+    /// In a transaction the statement would have to run on the pinned connection,
+    /// which is needed by the other statements of the transaction, so streaming is refused
+    fn stream_sql<'a>(&'a self, sql: &str, params: &[Value]) -> Result<Box<StreamStatement + 'a>, DbError> {
+        if self.is_transacted() {
+            return Err(DbError::new("Unable to stream the rows in a transaction, \
+                                     the unbuffered result would block the connection of the transaction"));
+        }
+        println!("SQL: \n{}", sql);
+        println!("param: {:?}", params);
+        let stmt = match self.pool {
+            Some(ref pool) => try!(pool.prepare(sql.to_owned())),
+            None => return Err(DbError::new("No connection for this database")),
+        };
+        let mut columns = vec![];
+        if let Some(columns_ref) = stmt.columns_ref() {
+            for col in columns_ref {
+                let column_name = String::from_utf8(col.name.clone()).unwrap();
+                columns.push((column_name, col.column_type));
+            }
+        }
+        Ok(Box::new(MysqlStreamStatement {
+            stmt: stmt,
+            params: params.to_vec(),
+            columns: columns,
+        }))
+    }
+
+    /// the statements are executed one at a time,
+    /// since multiple statements are not enabled on the connection
+    fn execute_batch(&self, sql: &str) -> Result<(), DbError> {
+        for statement in split_statements(sql) {
+            try!(self.execute_sql(&statement, &[]));
+        }
+        Ok(())
+    }
+
     /// generic execute sql which returns not much information,
     /// returns only the number of affected records or errors
     /// can be used with DDL operations (CREATE, DELETE, ALTER, DROP)
@@ -490,67 +832,269 @@ impl Database for Mysql {
         println!("param: {:?}", params);
         let to_sql_types = Mysql::from_rust_type_tosql(params);
         assert!(self.pool.is_some());
-        let result = try!(self.pool.as_ref().unwrap().prep_exec(sql, &to_sql_types));
+        let mut conn = self.conn.borrow_mut();
+        let result = match *conn {
+            Some(ref mut conn) => try!(conn.prep_exec(sql, &to_sql_types)),
+            None => try!(self.pool.as_ref().unwrap().prep_exec(sql, &to_sql_types)),
+        };
         Ok(result.affected_rows() as usize)
     }
 
 }
 
 impl DatabaseDDL for Mysql{
-    fn create_schema(&self, _schema: &str) {
-        unimplemented!()
+
+    /// schemas are databases in mysql
+    fn create_schema(&self, schema: &str, if_not_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_create_schema(schema, if_not_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
     }
 
-    fn drop_schema(&self, _schema: &str) {
-        unimplemented!()
+    fn build_create_schema(&self, schema: &str, if_not_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("CREATE SCHEMA ");
+        if if_not_exists {
+            w.append("IF NOT EXISTS ");
+        }
+        w.append(schema);
+        w
+    }
+
+    fn drop_schema(&self, schema: &str, if_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_drop_schema(schema, if_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
     }
 
-    fn build_create_table(&self, table: &Table) -> SqlFrag {
+    fn build_drop_schema(&self, schema: &str, if_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("DROP SCHEMA ");
+        if if_exists {
+            w.append("IF EXISTS ");
+        }
+        w.append(schema);
+        w
+    }
+
+    fn build_create_table(&self, table: &Table, if_not_exists: bool) -> SqlFrag {
         let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
         w.append("CREATE TABLE ");
-        w.append(&table.name);
-        w.append("(");
-        w.ln_tab();
+        if if_not_exists {
+            w.append("IF NOT EXISTS ");
+        }
+        w.append(&table.complete_name());
+        w.append(" (");
         let mut do_comma = false;
         for c in &table.columns {
             if do_comma {
-                w.commasp();
+                w.comma();
             } else {
                 do_comma = true;
             }
-            w.append(&c.name);
-            w.append(" ");
-            let dt = self.rust_type_to_dbtype(&c.data_type);
-            w.append(&dt);
-            if c.is_primary {
-                w.append(" PRIMARY KEY ");
+            w.ln_tab();
+            w.append(&self.column_definition(c));
+        }
+        let primary_columns: Vec<String> = table.primary_columns().iter().map(|c| c.name.to_owned()).collect();
+        if !primary_columns.is_empty() {
+            w.comma();
+            w.ln_tab();
+            w.append(&format!("PRIMARY KEY ({})", primary_columns.join(", ")));
+        }
+        for c in &table.columns {
+            if c.is_unique && !c.is_primary {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("CONSTRAINT {}_{}_key UNIQUE ({})", table.name, c.name, c.name));
             }
         }
+        for c in &table.columns {
+            if let Some(ref foreign) = c.foreign {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                                  table.name,
+                                  c.name,
+                                  c.name,
+                                  foreign.complete_table_name(),
+                                  foreign.column));
+            }
+        }
+        w.ln();
         w.append(")");
+        if let Some(ref comment) = table.comment {
+            w.append(" COMMENT = ");
+            w.append(&quote_literal(comment));
+        }
         w
     }
-    fn create_table(&self, table: &Table) {
-        let frag = self.build_create_table(table);
-        match self.execute_sql(&frag.sql, &vec![]) {
-            Ok(_) => println!("created table.."),
-            Err(e) => panic!("table not created {}", e),
+
+    fn create_table(&self, table: &Table, if_not_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_create_table(table, if_not_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    fn rename_table(&self, table: &Table, new_tablename: String) -> Result<(), DbError> {
+        let frag = self.build_rename_table(table, &new_tablename);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    /// the table stays in the same schema
+    fn build_rename_table(&self, table: &Table, new_tablename: &str) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("RENAME TABLE ");
+        w.append(&table.complete_name());
+        w.append(" TO ");
+        match table.schema {
+            Some(ref schema) => w.append(&format!("{}.{}", schema, new_tablename)),
+            None => w.append(new_tablename),
+        };
+        w
+    }
+
+    fn drop_table(&self, table: &Table, if_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_drop_table(table, if_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    fn build_drop_table(&self, table: &Table, if_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("DROP TABLE ");
+        if if_exists {
+            w.append("IF EXISTS ");
         }
+        w.append(&table.complete_name());
+        w
     }
 
-    fn rename_table(&self, _table: &Table, _new_tablename: String) {
-        unimplemented!()
+    fn set_foreign_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_foreign_constraint(model);
+        if frag.sql.is_empty() {
+            return Ok(());
+        }
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
     }
 
-    fn drop_table(&self, _table: &Table) {
-        unimplemented!()
+    /// empty when the table has no foreign columns
+    fn build_foreign_constraint(&self, model: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        let foreign_columns = model.foreign_columns();
+        if foreign_columns.is_empty() {
+            return w;
+        }
+        w.append("ALTER TABLE ");
+        w.append(&model.complete_name());
+        let mut do_comma = false;
+        for c in foreign_columns {
+            if let Some(ref foreign) = c.foreign {
+                if do_comma {
+                    w.comma();
+                } else {
+                    do_comma = true;
+                }
+                w.ln_tab();
+                w.append(&format!("ADD CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                                  model.name,
+                                  c.name,
+                                  c.name,
+                                  foreign.complete_table_name(),
+                                  foreign.column));
+            }
+        }
+        w
     }
 
-    fn set_foreign_constraint(&self, _model: &Table) {
-        unimplemented!()
+    fn set_primary_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_primary_constraint(model);
+        if frag.sql.is_empty() {
+            return Ok(());
+        }
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
     }
 
-    fn set_primary_constraint(&self, _model: &Table) {
-        unimplemented!()
+    /// empty when the table has no primary columns
+    fn build_primary_constraint(&self, model: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        let primary_columns: Vec<String> = model.primary_columns().iter().map(|c| c.name.to_owned()).collect();
+        if primary_columns.is_empty() {
+            return w;
+        }
+        w.append("ALTER TABLE ");
+        w.append(&model.complete_name());
+        w.ln_tab();
+        w.append(&format!("ADD PRIMARY KEY ({})", primary_columns.join(", ")));
+        w
+    }
+
+    fn build_add_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("ADD COLUMN ");
+        w.append(&self.column_definition(column));
+        w
+    }
+
+    fn build_drop_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("DROP COLUMN ");
+        w.append(&column.name);
+        w
+    }
+
+    fn build_alter_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("MODIFY COLUMN ");
+        w.append(&self.column_definition(column));
+        w
+    }
+
+    /// empty when the column is not a foreign key
+    fn build_add_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        if let Some(ref foreign) = column.foreign {
+            w.append("ALTER TABLE ");
+            w.append(&table.complete_name());
+            w.ln_tab();
+            w.append(&format!("ADD CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                              table.name,
+                              column.name,
+                              column.name,
+                              foreign.complete_table_name(),
+                              foreign.column));
+        }
+        w
+    }
+
+    /// the constraint is expected to be named as in `build_create_table`
+    fn build_drop_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append(&format!("DROP FOREIGN KEY {}_{}_fkey", table.name, column.name));
+        w
+    }
+
+    fn build_drop_primary_constraint(&self, table: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("DROP PRIMARY KEY");
+        w
     }
 }
 
@@ -690,58 +1234,7 @@ impl DatabaseDev for Mysql {
     /// will be used in generating SQL for table creation
     /// FIXME, need to restore the exact data type as before
     fn rust_type_to_dbtype(&self, rust_type: &Type) -> String {
-        match *rust_type {
-            Type::Bool => {
-                "bool".to_owned()
-            }
-            Type::I8 => {
-                "tinyint(1)".to_owned()
-            }
-            Type::I16 => {
-                "integer".to_owned()
-            }
-            Type::I32 => {
-                "integer".to_owned()
-            }
-            Type::U32 => {
-                "integer".to_owned()
-            }
-            Type::I64 => {
-                "integer".to_owned()
-            }
-            Type::F32 => {
-                "real".to_owned()
-            }
-            Type::F64 => {
-                "real".to_owned()
-            }
-            Type::String => {
-                "text".to_owned()
-            }
-            Type::VecU8 => {
-                "blob".to_owned()
-            }
-            Type::Json => {
-                "text".to_owned()
-            }
-            Type::Uuid => {
-                "varchar(36)".to_owned()
-            }
-            Type::NaiveDateTime => {
-                "timestamp".to_owned()
-            }
-            Type::DateTime => {
-                "timestamp".to_owned()
-            }
-            Type::NaiveDate => {
-                "date".to_owned()
-            }
-            Type::NaiveTime => {
-                "time".to_owned()
-            }
-            _ => panic!("Unable to get the equivalent database data type for {:?}",
-                        rust_type),
-        }
+        Mysql::rust_type_to_dbtype(self, rust_type)
     }
 
 }
@@ -1,15 +1,19 @@
 use query::Query;
 use table::{Table, Column, Foreign};
-use dao::Dao;
+use dao::{Dao, ColumnInfo, Rows};
 
 use postgres::Connection;
 use regex::Regex;
 use dao::Value;
-use database::{Database, DatabaseDev, DatabaseDDL, DbError};
+use database::{Database, DatabaseDev, DatabaseDDL, DbError, DaoStream, StreamStatement};
+use database::{default_sql, quote_literal};
+use database::BuildMode;
 use postgres::types::Type as PgType;
 use postgres::types::ToSql;
 use writer::SqlFrag;
-use postgres::rows::Row;
+use postgres::rows::{Row, LazyRows};
+use postgres::stmt::Statement;
+use postgres::transaction::Transaction;
 use database::SqlOption;
 use r2d2::PooledConnection;
 use r2d2_postgres::PostgresConnectionManager;
@@ -18,10 +22,77 @@ use dao::Type;
 use postgres::types::IsNull;
 use uuid::Uuid;
 use query::Operand;
+use transaction::savepoint_name;
+use std::cell::Cell;
+use postgres::error::Error as PgError;
+use database::ErrorKind;
+use dao::IsDao;
+use table::IsTable;
+use std::io::Write;
+use platform::copy::{CopyFormat, CopyReader, inline_params};
 
 pub struct Postgres {
     /// a connection pool is provided
     pub pool: Option<PooledConnection<PostgresConnectionManager>>,
+    /// the number of nested transactions currently active on this connection
+    transaction_depth: Cell<usize>,
+}
+
+/// the number of rows fetched from a portal at a time
+const FETCH_SIZE: i32 = 1000;
+
+/// a statement prepared for its rows to be streamed,
+/// with the transaction its portal lives in, which is rolled back when the statement is dropped.
+/// The transaction counts as one level of the transaction depth while the statement lives,
+/// so transactions begun meanwhile are savepoints in it
+pub struct PgStreamStatement<'a> {
+    pg: &'a Postgres,
+    // declared before the transaction, so the statement is closed first
+    stmt: Statement<'a>,
+    transaction: Transaction<'a>,
+    params: Vec<Value>,
+}
+
+impl <'a>Drop for PgStreamStatement<'a> {
+    fn drop(&mut self) {
+        let depth = self.pg.transaction_depth.get();
+        self.pg.transaction_depth.set(depth - 1);
+    }
+}
+
+impl <'a>StreamStatement for PgStreamStatement<'a> {
+    fn rows<'s>(&'s mut self) -> Result<DaoStream<'s>, DbError> {
+        let param = self.pg.from_rust_type_tosql(&self.params);
+        let rows = try!(self.stmt.lazy_query(&self.transaction, &param, FETCH_SIZE));
+        Ok(Box::new(PgStream {
+            pg: self.pg,
+            rows: rows,
+        }))
+    }
+}
+
+/// the rows of a portal, fetched from the server a batch at a time
+pub struct PgStream<'s> {
+    pg: &'s Postgres,
+    rows: LazyRows<'s, 's>,
+}
+
+impl <'s>Iterator for PgStream<'s> {
+    type Item = Result<Dao, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match self.rows.next() {
+            Some(Ok(row)) => row,
+            Some(Err(e)) => return Some(Err(DbError::from(e))),
+            None => return None,
+        };
+        let mut dao = Dao::new();
+        for (index, column) in row.columns().iter().enumerate() {
+            let rtype = self.pg.from_sql_to_rust_type(column.type_(), &row, index);
+            dao.set_value(column.name(), rtype);
+        }
+        Some(Ok(dao))
+    }
 }
 
 /// Build the Query into a SQL statements that is a valid
@@ -36,16 +107,42 @@ impl Postgres {
     /// useful when just building sql queries specific to this platform
     /// inexpensive operation, so can have multiple instances
     pub fn new() -> Self {
-        Postgres { pool: None }
+        Postgres {
+            pool: None,
+            transaction_depth: Cell::new(0),
+        }
     }
 
 
     pub fn with_pooled_connection(pool: PooledConnection<PostgresConnectionManager>) -> Self {
-        Postgres { pool: Some(pool) }
+        Postgres {
+            pool: Some(pool),
+            transaction_depth: Cell::new(0),
+        }
     }
 
 
 
+    /// the column name, type, NOT NULL and DEFAULT used in CREATE TABLE and ADD COLUMN
+    fn column_definition(&self, column: &Column) -> String {
+        let mut definition = format!("{} {}", column.name, self.rust_type_to_dbtype(&column.data_type));
+        if column.not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(ref default) = column.default {
+            if let Some(default) = default_sql(default) {
+                definition.push_str(" DEFAULT ");
+                definition.push_str(&default);
+            }
+        }
+        definition
+    }
+
+    /// execute DDL statements, which may contain several statements separated with `;`
+    fn execute_ddl(&self, frag: &SqlFrag) -> Result<(), DbError> {
+        self.execute_batch(&frag.sql)
+    }
+
     pub fn get_connection(&self) -> &Connection {
         match self.pool {
             Some(ref pool) => &pool,
@@ -53,6 +150,49 @@ impl Postgres {
         }
     }
 
+    /// bulk load the daos into the table with COPY FROM STDIN, returns the number of rows copied.
+    /// The columns are those of the table which are present in the first dao,
+    /// values missing in the other daos are copied as NULL.
+    /// The daos are encoded as they are consumed, so the iterator can be larger than the memory
+    pub fn copy_in<I>(&self, table: &Table, daos: I, format: CopyFormat) -> Result<u64, DbError>
+        where I: IntoIterator<Item = Dao>
+    {
+        let mut reader = CopyReader::new(table, daos.into_iter(), format);
+        let columns = reader.columns().join(", ");
+        if columns.is_empty() {
+            return Ok(0);
+        }
+        let sql = format!("COPY {}({}) FROM STDIN{}", table.complete_name(), columns, format.options());
+        println!("SQL: \n{}", sql);
+        let conn = self.get_connection();
+        let stmt = try!(conn.prepare(&sql));
+        let rows = try!(stmt.copy_in(&[], &mut reader));
+        Ok(rows)
+    }
+
+    /// bulk load the records into the table of the model with COPY FROM STDIN
+    pub fn copy_in_records<T, I>(&self, records: I, format: CopyFormat) -> Result<u64, DbError>
+        where T: IsDao + IsTable,
+              I: IntoIterator<Item = T>
+    {
+        self.copy_in(&T::table(), records.into_iter().map(|r| r.to_dao()), format)
+    }
+
+    /// export the result of the query with COPY (query) TO STDOUT into the writer,
+    /// returns the number of rows written.
+    /// COPY does not accept parameters, the values of the query are inlined as literals
+    pub fn copy_out<W: Write>(&self, query: &mut Query, format: CopyFormat, writer: &mut W) -> Result<u64, DbError> {
+        let frag = try!(query.build(self));
+        let sql = format!("COPY ({}) TO STDOUT{}",
+                          inline_params(frag.sql.trim(), &frag.params),
+                          format.options());
+        println!("SQL: \n{}", sql);
+        let conn = self.get_connection();
+        let stmt = try!(conn.prepare(&sql));
+        let rows = try!(stmt.copy_out(&[], writer));
+        Ok(rows)
+    }
+
     /// convert Type to ToSql (postgresql native types)
     /// This is used when inserting records to the database
     /// TODO: put this somewhere organized
@@ -111,113 +251,113 @@ impl Postgres {
             PgType::Uuid => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::Uuid(value),
-                    Err(_) => Value::None(Type::Uuid),
+                    Some(Ok(value)) => Value::Uuid(value),
+                    _ => Value::None(Type::Uuid),
                 }
             }
             PgType::Varchar | PgType::Text | PgType::Bpchar => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::String(value),
-                    Err(_) => Value::None(Type::String),
+                    Some(Ok(value)) => Value::String(value),
+                    _ => Value::None(Type::String),
                 }
             }
             PgType::TimestampTZ | PgType::Timestamp => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::DateTime(value),
-                    Err(_) => Value::None(Type::DateTime),
+                    Some(Ok(value)) => Value::DateTime(value),
+                    _ => Value::None(Type::DateTime),
                 }
             }
             PgType::Float4 => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::F32(value),
-                    Err(_) => Value::None(Type::F32),
+                    Some(Ok(value)) => Value::F32(value),
+                    _ => Value::None(Type::F32),
                 }
             }
             PgType::Float8 => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::F64(value),
-                    Err(_) => Value::None(Type::F64),
+                    Some(Ok(value)) => Value::F64(value),
+                    _ => Value::None(Type::F64),
                 }
             }
             PgType::Numeric => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::F64(value),
-                    Err(_) => Value::None(Type::F64),
+                    Some(Ok(value)) => Value::F64(value),
+                    _ => Value::None(Type::F64),
                 }
             }, 
             PgType::Bool => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::Bool(value),
-                    Err(_) => Value::None(Type::F64),
+                    Some(Ok(value)) => Value::Bool(value),
+                    _ => Value::None(Type::F64),
                 }
             }
             PgType::Json => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::Json(value),
-                    Err(_) => Value::None(Type::F64),
+                    Some(Ok(value)) => Value::Json(value),
+                    _ => Value::None(Type::F64),
                 }
             }
             PgType::Int2 => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::I16(value),
-                    Err(_) => Value::None(Type::F64),
+                    Some(Ok(value)) => Value::I16(value),
+                    _ => Value::None(Type::F64),
                 }
             }
             PgType::Int4 => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::I32(value),
-                    Err(_) => Value::None(Type::I32),
+                    Some(Ok(value)) => Value::I32(value),
+                    _ => Value::None(Type::I32),
                 }
             }
             PgType::Int8 => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::I64(value),
-                    Err(_) => Value::None(Type::I64),
+                    Some(Ok(value)) => Value::I64(value),
+                    _ => Value::None(Type::I64),
                 }
             }
             PgType::Timetz => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::DateTime(value),
-                    Err(_) => Value::None(Type::DateTime),
+                    Some(Ok(value)) => Value::DateTime(value),
+                    _ => Value::None(Type::DateTime),
                 }
             }
             PgType::Date => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::DateTime(value),
-                    Err(_) => Value::None(Type::DateTime),
+                    Some(Ok(value)) => Value::DateTime(value),
+                    _ => Value::None(Type::DateTime),
                 }
             }
             PgType::Bytea => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::VecU8(value),
-                    Err(_) => Value::None(Type::VecU8),
+                    Some(Ok(value)) => Value::VecU8(value),
+                    _ => Value::None(Type::VecU8),
                 }
             }
             PgType::Inet => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::String(value),
-                    Err(_) => Value::None(Type::String),
+                    Some(Ok(value)) => Value::String(value),
+                    _ => Value::None(Type::String),
                 }
             }
             PgType::Tsvector => {
                 let value = row.get_opt(index);
                 match value {
-                    Ok(value) => Value::String(value),
-                    Err(_) => Value::None(Type::String),
+                    Some(Ok(value)) => Value::String(value),
+                    _ => Value::None(Type::String),
                 }
             }
             _ => panic!("Type {:?} is not covered!", dtype),
@@ -225,6 +365,26 @@ impl Postgres {
     }
 
 
+    /// the generic type the values of a postgres type are converted to,
+    /// the same as the conversion done in `from_sql_to_rust_type`
+    fn from_pg_type_to_rust_type(dtype: &PgType) -> Type {
+        match *dtype {
+            PgType::Uuid => Type::Uuid,
+            PgType::Varchar | PgType::Text | PgType::Bpchar => Type::String,
+            PgType::TimestampTZ | PgType::Timestamp => Type::DateTime,
+            PgType::Float4 => Type::F32,
+            PgType::Float8 | PgType::Numeric => Type::F64,
+            PgType::Bool => Type::Bool,
+            PgType::Json => Type::Json,
+            PgType::Int2 => Type::I16,
+            PgType::Int4 => Type::I32,
+            PgType::Int8 => Type::I64,
+            PgType::Timetz | PgType::Date => Type::DateTime,
+            PgType::Bytea => Type::VecU8,
+            _ => Type::String,
+        }
+    }
+
     ///
     /// http://stackoverflow.com/questions/109325/postgresql-describe-table
     ///
@@ -247,7 +407,7 @@ impl Postgres {
                 WHEN pg_constraint.contype = 'f' THEN g.relname
                 END AS foreign_table,
                 CASE
-                WHEN pg_attribute.atthasdef = true THEN pg_attrdef.adsrc
+                WHEN pg_attribute.atthasdef = true THEN pg_get_expr(pg_attrdef.adbin, pg_attrdef.adrelid)
                 END as default
                 ,pg_description.description as comment
                 ,(SELECT nspname FROM pg_namespace WHERE oid=g.relnamespace) AS foreign_schema
@@ -284,7 +444,7 @@ impl Postgres {
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
         let mut columns = Vec::new();
-        for row in stmt.query(&[&schema, &table]).unwrap() {
+        for row in stmt.query(&[&schema, &table]).unwrap().iter() {
             let name: String = row.get("name");
             let not_null: bool = row.get("notnull");
             let db_data_type: String = row.get("data_type");
@@ -308,25 +468,25 @@ impl Postgres {
             let is_unique: bool = row.get("is_unique");
 
             let default: Option<Operand> = match row.get_opt("default") {
-                Ok(x) => Some(Operand::Value(Value::String(x))),
-                Err(_) => None,
+                Some(Ok(x)) => Some(Operand::Value(Value::String(x))),
+                _ => None,
             };
             let comment: Option<String> = match row.get_opt("comment") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
 
             let foreign_schema: Option<String> = match row.get_opt("foreign_schema") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
             let foreign_column: Option<String> = match row.get_opt("foreign_column") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
             let foreign_table: Option<String> = match row.get_opt("foreign_table") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
 
 
@@ -379,10 +539,10 @@ impl Postgres {
                 ";
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
-        for row in stmt.query(&[&schema, &table]).unwrap() {
+        for row in stmt.query(&[&schema, &table]).unwrap().iter() {
             let comment: Option<String> = match row.get_opt("comment") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
             return comment;
         }
@@ -418,9 +578,127 @@ impl Postgres {
 
     }
 
+
+    /// the rust data type names and type of the database data type,
+    /// None when there is no equivalent rust type
+    fn equivalent_rust_type(&self, db_type: &str) -> Option<(Vec<String>, Type)> {
+        let equivalent = match db_type {
+            "boolean" => {
+                (vec![], Type::Bool)
+            }
+            "char" => {
+                (vec![], Type::I8)
+            }
+            "smallint" | "smallserial" => {
+                (vec![], Type::I16)
+            }
+            "integer" | "int" | "serial" => {
+                (vec![], Type::I32)
+            }
+            "oid" => {
+                (vec![], Type::U32)
+            }
+            "bigint" | "bigserial" => {
+                (vec![], Type::I64)
+            }
+            "real" => {
+                (vec![], Type::F32)
+            }
+            "numeric" => {
+                (vec![], Type::F64)
+                //panic!("No support for numeric yet, please use real or double precision")
+            }
+            "double precision" => {
+                (vec![], Type::F64)
+            }
+            "name" | "character" | "character varying" | "text" | "citext" | "bpchar" => {
+                (vec![], Type::String)
+            }
+            "bytea" => {
+                (vec![], Type::VecU8)
+            }
+            "json" | "jsonb" => {
+                (vec!["rustc_serialize::json::Json".to_owned()], Type::Json)
+            }
+            "uuid" => {
+                (vec!["uuid::Uuid".to_owned()], Type::Uuid)
+            }
+            "timestamp" => {
+                (vec!["chrono::naive::datetime::NaiveDateTime".to_owned()],
+                 Type::NaiveDateTime)
+            }
+            "timestamp without time zone" => {
+                (vec!["chrono::naive::datetime::NaiveDateTime".to_owned()],
+                 Type::NaiveDateTime)
+            }
+            "timestamp with time zone" => {
+                (vec!["chrono::datetime::DateTime".to_owned(),
+                      "chrono::offset::utc::UTC".to_owned()],
+                 Type::DateTime)
+            }
+            "time with time zone" => {
+                (vec!["chrono::naive::time::NaiveTime".to_owned(),
+                      "chrono::offset::utc::UTC".to_owned()],
+                 Type::NaiveTime)
+            }
+            "date" => {
+                (vec!["chrono::naive::date::NaiveDate".to_owned()],
+                 Type::NaiveDate)
+            }
+            "time" | "time without time zone" => {
+                (vec!["chrono::naive::time::NaiveTime".to_owned()],
+                 Type::NaiveTime)
+            }
+            "hstore" => {
+                (vec!["std::collections::HashMap".to_owned()],
+                 Type::Object)
+            }
+            "interval" => {
+                (vec![], Type::U32)
+            }
+            "inet[]" => {
+                (vec![], Type::String)
+            }
+            "tsvector" | "inet" => {
+                (vec![], Type::String)
+            }//or everything else should be string
+            _ if db_type.ends_with("[]") => {
+                // arrays are retrieved in their text representation ie: {a,b}
+                (vec![], Type::String)
+            }
+            _ => return None,
+        };
+        Some(equivalent)
+    }
+
 }
 
 
+/// the kind of error from the SQLSTATE code returned by the server
+pub fn error_kind(err: &PgError) -> ErrorKind {
+    match *err {
+        PgError::Db(ref db_error) => ErrorKind::from_sqlstate(db_error.code.code()),
+        PgError::Io(_) => ErrorKind::ConnectionLost,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// the name of the violated constraint, as reported by the server
+pub fn error_constraint(err: &PgError) -> Option<String> {
+    match *err {
+        PgError::Db(ref db_error) => db_error.constraint.clone(),
+        _ => None,
+    }
+}
+
+/// the name of the table involved in the error, as reported by the server
+pub fn error_table(err: &PgError) -> Option<String> {
+    match *err {
+        PgError::Db(ref db_error) => db_error.table.clone(),
+        _ => None,
+    }
+}
+
 impl Database for Postgres {
     fn version(&self) -> Result<String, DbError> {
         let sql = "SHOW server_version";
@@ -431,14 +709,47 @@ impl Database for Postgres {
         }
     }
 
-    fn begin(&self) {
+    fn begin(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            try!(self.execute_sql("BEGIN", &[]));
+        } else {
+            try!(self.execute_sql(&format!("SAVEPOINT {}", savepoint_name(depth)), &[]));
+        }
+        self.transaction_depth.set(depth + 1);
+        Ok(())
     }
-    fn commit(&self) {
+    fn commit(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to commit, there is no active transaction"));
+        }
+        // the transaction is still open when the commit fails, ie: a deferred constraint is violated
+        if depth == 1 {
+            try!(self.execute_sql("COMMIT", &[]));
+        } else {
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint_name(depth - 1)), &[]));
+        }
+        self.transaction_depth.set(depth - 1);
+        Ok(())
     }
-    fn rollback(&self) {
+    fn rollback(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to rollback, there is no active transaction"));
+        }
+        self.transaction_depth.set(depth - 1);
+        if depth == 1 {
+            try!(self.execute_sql("ROLLBACK", &[]));
+        } else {
+            let savepoint = savepoint_name(depth - 1);
+            try!(self.execute_sql(&format!("ROLLBACK TO SAVEPOINT {}", savepoint), &[]));
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint), &[]));
+        }
+        Ok(())
     }
     fn is_transacted(&self) -> bool {
-        false
+        self.transaction_depth.get() > 0
     }
     fn is_closed(&self) -> bool {
         false
@@ -470,9 +781,20 @@ impl Database for Postgres {
             SqlOption::SupportsInheritance,
             SqlOption::UsesSchema,
             SqlOption::ReturnMetaColumns,// whether to use the column names returned in a statement
+            SqlOption::SupportsDistinctOn,
+            SqlOption::SupportsOnConflict,
+            SqlOption::SupportsIntersectExcept,
+            SqlOption::SupportsTransactionalDDL,
+            SqlOption::SupportsDefaultKeyword,
+            SqlOption::SupportsWindowFunctions,
         ]
     }
 
+    /// the bind message of the protocol limits the parameters to an u16
+    fn max_parameters(&self) -> usize {
+        65535
+    }
+
 
     fn update(&self, _query: &Query) -> Dao {
         unimplemented!()
@@ -481,28 +803,61 @@ impl Database for Postgres {
         unimplemented!()
     }
 
-    fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError> {
+    /// the source table of the columns is not reported by the driver
+    fn execute_sql_with_columns(&self, sql: &str, params: &[Value]) -> Result<Rows, DbError> {
         println!("SQL: \n{}", sql);
         println!("param: {:?}", params);
         let conn = self.get_connection();
         let stmt = try!(conn.prepare(sql));
-        let mut daos = vec![];
+        let mut columns = vec![];
+        for c in stmt.columns() {
+            columns.push(ColumnInfo {
+                name: c.name().to_owned(),
+                table: None,
+                db_type: c.type_().name().to_owned(),
+                data_type: Postgres::from_pg_type_to_rust_type(c.type_()),
+            });
+        }
+        let mut result = Rows::new(columns);
         let param = self.from_rust_type_tosql(params);
         let rows = try!(stmt.query(&param));
-        for row in rows {
-            let columns = row.columns();
-            let mut index = 0;
-            let mut dao = Dao::new();
-            for c in columns {
-                let column_name = c.name();
-                let dtype = c.type_();
-                let rtype = self.from_sql_to_rust_type(&dtype, &row, index);
-                dao.set_value(column_name, rtype);
-                index += 1;
+        for row in rows.iter() {
+            let mut values = vec![];
+            for (index, c) in row.columns().iter().enumerate() {
+                values.push(self.from_sql_to_rust_type(c.type_(), &row, index));
             }
-            daos.push(dao);
+            result.push(values);
         }
-        Ok(daos)
+        Ok(result)
+    }
+
+    fn execute_batch(&self, sql: &str) -> Result<(), DbError> {
+        println!("SQL: \n{}", sql);
+        let conn = self.get_connection();
+        try!(conn.batch_execute(sql));
+        Ok(())
+    }
+
+    /// the rows are read from a portal, which only exists inside a transaction,
+    /// the transaction is started for the statement and ends with it.
+    /// Streaming is refused inside another transaction, ending the transaction of the portal would end it too
+    fn stream_sql<'a>(&'a self, sql: &str, params: &[Value]) -> Result<Box<StreamStatement + 'a>, DbError> {
+        if self.is_transacted() {
+            return Err(DbError::new("Unable to stream the rows in a transaction or while another stream is open, \
+                                     the portal needs a transaction of its own"));
+        }
+        println!("SQL: \n{}", sql);
+        println!("param: {:?}", params);
+        let conn = self.get_connection();
+        let transaction = try!(conn.transaction());
+        let stmt = try!(conn.prepare(sql));
+        self.transaction_depth.set(1);
+        Ok(Box::new(PgStreamStatement {
+            pg: self,
+            stmt: stmt,
+            transaction: transaction,
+            params: params.to_vec(),
+        }))
     }
 
     /// generic execute sql which returns not much information,
@@ -521,29 +876,285 @@ impl Database for Postgres {
 
 impl DatabaseDDL for Postgres {
 
-    fn create_schema(&self, _schema: &str) {
-        unimplemented!()
+    fn create_schema(&self, schema: &str, if_not_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_create_schema(schema, if_not_exists);
+        self.execute_ddl(&frag)
     }
-    fn drop_schema(&self, _schema: &str) {
-        unimplemented!()
+
+    fn build_create_schema(&self, schema: &str, if_not_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("CREATE SCHEMA ");
+        if if_not_exists {
+            w.append("IF NOT EXISTS ");
+        }
+        w.append(schema);
+        w
     }
-    fn create_table(&self, _model: &Table) {
-        unimplemented!()
+
+    fn drop_schema(&self, schema: &str, if_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_drop_schema(schema, if_exists);
+        self.execute_ddl(&frag)
     }
-    fn build_create_table(&self, _table: &Table) -> SqlFrag {
-        unimplemented!()
+
+    fn build_drop_schema(&self, schema: &str, if_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("DROP SCHEMA ");
+        if if_exists {
+            w.append("IF EXISTS ");
+        }
+        w.append(schema);
+        w
     }
-    fn rename_table(&self, _table: &Table, _new_tablename: String) {
-        unimplemented!()
+
+    fn create_table(&self, table: &Table, if_not_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_create_table(table, if_not_exists);
+        self.execute_ddl(&frag)
     }
-    fn drop_table(&self, _table: &Table) {
-        unimplemented!()
+
+    /// the primary and unique constraints are named the same way postgresql does,
+    /// ie: product_pkey, product_name_key, product_owner_id_fkey.
+    /// Comments are added with COMMENT ON statements following the CREATE TABLE
+    fn build_create_table(&self, table: &Table, if_not_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("CREATE TABLE ");
+        if if_not_exists {
+            w.append("IF NOT EXISTS ");
+        }
+        w.append(&table.complete_name());
+        w.append(" (");
+        let mut do_comma = false;
+        for c in table.uninherited_columns() {
+            if do_comma {
+                w.comma();
+            } else {
+                do_comma = true;
+            }
+            w.ln_tab();
+            w.append(&self.column_definition(c));
+        }
+        let primary_columns: Vec<String> = table.primary_columns().iter().map(|c| c.name.to_owned()).collect();
+        if !primary_columns.is_empty() {
+            w.comma();
+            w.ln_tab();
+            w.append(&format!("CONSTRAINT {}_pkey PRIMARY KEY ({})", table.name, primary_columns.join(", ")));
+        }
+        for c in table.uninherited_columns() {
+            if c.is_unique && !c.is_primary {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("CONSTRAINT {}_{}_key UNIQUE ({})", table.name, c.name, c.name));
+            }
+        }
+        for c in table.uninherited_columns() {
+            if let Some(ref foreign) = c.foreign {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                                  table.name,
+                                  c.name,
+                                  c.name,
+                                  foreign.complete_table_name(),
+                                  foreign.column));
+            }
+        }
+        w.ln();
+        w.append(")");
+        if let Some(ref parent_table) = table.parent_table {
+            w.append(" INHERITS (");
+            match table.schema {
+                Some(ref schema) if !parent_table.contains('.') => {
+                    w.append(&format!("{}.{}", schema, parent_table));
+                }
+                _ => {
+                    w.append(parent_table);
+                }
+            }
+            w.append(")");
+        }
+        w.append(";");
+        if let Some(ref comment) = table.comment {
+            w.ln();
+            w.append(&format!("COMMENT ON TABLE {} IS {};", table.complete_name(), quote_literal(comment)));
+        }
+        for c in table.uninherited_columns() {
+            if let Some(ref comment) = c.comment {
+                w.ln();
+                w.append(&format!("COMMENT ON COLUMN {}.{} IS {};",
+                                  table.complete_name(),
+                                  c.name,
+                                  quote_literal(comment)));
+            }
+        }
+        w
     }
-    fn set_foreign_constraint(&self, _model: &Table) {
-        unimplemented!()
+
+    fn rename_table(&self, table: &Table, new_tablename: String) -> Result<(), DbError> {
+        let frag = self.build_rename_table(table, &new_tablename);
+        self.execute_ddl(&frag)
     }
-    fn set_primary_constraint(&self, _model: &Table) {
-        unimplemented!()
+
+    fn build_rename_table(&self, table: &Table, new_tablename: &str) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.append(" RENAME TO ");
+        w.append(new_tablename);
+        w
+    }
+
+    fn drop_table(&self, table: &Table, if_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_drop_table(table, if_exists);
+        self.execute_ddl(&frag)
+    }
+
+    fn build_drop_table(&self, table: &Table, if_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("DROP TABLE ");
+        if if_exists {
+            w.append("IF EXISTS ");
+        }
+        w.append(&table.complete_name());
+        w
+    }
+
+    fn set_foreign_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_foreign_constraint(model);
+        if frag.sql.is_empty() {
+            return Ok(());
+        }
+        self.execute_ddl(&frag)
+    }
+
+    /// empty when the table has no foreign columns
+    fn build_foreign_constraint(&self, model: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        let foreign_columns = model.foreign_columns();
+        if foreign_columns.is_empty() {
+            return w;
+        }
+        w.append("ALTER TABLE ");
+        w.append(&model.complete_name());
+        let mut do_comma = false;
+        for c in foreign_columns {
+            if let Some(ref foreign) = c.foreign {
+                if do_comma {
+                    w.comma();
+                } else {
+                    do_comma = true;
+                }
+                w.ln_tab();
+                w.append(&format!("ADD CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                                  model.name,
+                                  c.name,
+                                  c.name,
+                                  foreign.complete_table_name(),
+                                  foreign.column));
+            }
+        }
+        w
+    }
+
+    fn set_primary_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_primary_constraint(model);
+        if frag.sql.is_empty() {
+            return Ok(());
+        }
+        self.execute_ddl(&frag)
+    }
+
+    /// empty when the table has no primary columns
+    fn build_primary_constraint(&self, model: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        let primary_columns: Vec<String> = model.primary_columns().iter().map(|c| c.name.to_owned()).collect();
+        if primary_columns.is_empty() {
+            return w;
+        }
+        w.append("ALTER TABLE ");
+        w.append(&model.complete_name());
+        w.ln_tab();
+        w.append(&format!("ADD CONSTRAINT {}_pkey PRIMARY KEY ({})", model.name, primary_columns.join(", ")));
+        w
+    }
+
+    fn build_add_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("ADD COLUMN ");
+        w.append(&self.column_definition(column));
+        w
+    }
+
+    fn build_drop_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append("DROP COLUMN ");
+        w.append(&column.name);
+        w
+    }
+
+    /// the existing values are cast to the new type
+    fn build_alter_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        let data_type = self.rust_type_to_dbtype(&column.data_type);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append(&format!("ALTER COLUMN {} TYPE {} USING {}::{}", column.name, data_type, column.name, data_type));
+        w.comma();
+        w.ln_tab();
+        if column.not_null {
+            w.append(&format!("ALTER COLUMN {} SET NOT NULL", column.name));
+        } else {
+            w.append(&format!("ALTER COLUMN {} DROP NOT NULL", column.name));
+        }
+        w.comma();
+        w.ln_tab();
+        match column.default.as_ref().and_then(default_sql) {
+            Some(default) => w.append(&format!("ALTER COLUMN {} SET DEFAULT {}", column.name, default)),
+            None => w.append(&format!("ALTER COLUMN {} DROP DEFAULT", column.name)),
+        };
+        w
+    }
+
+    /// empty when the column is not a foreign key
+    fn build_add_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        if let Some(ref foreign) = column.foreign {
+            w.append("ALTER TABLE ");
+            w.append(&table.complete_name());
+            w.ln_tab();
+            w.append(&format!("ADD CONSTRAINT {}_{}_fkey FOREIGN KEY ({}) REFERENCES {} ({})",
+                              table.name,
+                              column.name,
+                              column.name,
+                              foreign.complete_table_name(),
+                              foreign.column));
+        }
+        w
+    }
+
+    /// the constraint is expected to have the default name given by postgresql
+    fn build_drop_foreign_constraint(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append(&format!("DROP CONSTRAINT {}_{}_fkey", table.name, column.name));
+        w
+    }
+
+    /// the constraint is expected to have the default name given by postgresql
+    fn build_drop_primary_constraint(&self, table: &Table) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.complete_name());
+        w.ln_tab();
+        w.append(&format!("DROP CONSTRAINT {}_pkey", table.name));
+        w
     }
 
 }
@@ -567,10 +1178,10 @@ impl DatabaseDev for Postgres {
                 ";
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
-        for row in stmt.query(&[&schema, &table]).unwrap() {
+        for row in stmt.query(&[&schema, &table]).unwrap().iter() {
             let parent_table: Option<String> = match row.get_opt("parent_table") {
-                Ok(x) => Some(x),
-                Err(_) => None,
+                Some(Ok(x)) => Some(x),
+                _ => None,
             };
             return parent_table;
         }
@@ -594,10 +1205,10 @@ impl DatabaseDev for Postgres {
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
         let mut sub_classes: Vec<String> = vec![];
-        for row in stmt.query(&[&schema, &table]).unwrap() {
+        for row in stmt.query(&[&schema, &table]).unwrap().iter() {
             match row.get_opt("sub_class") {
-                Ok(x) => sub_classes.push(x),
-                Err(_) => (),
+                Some(Ok(x)) => sub_classes.push(x),
+                _ => (),
             }
         }
         sub_classes
@@ -657,7 +1268,7 @@ impl DatabaseDev for Postgres {
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
         let mut tables: Vec<(String, String, bool)> = Vec::new();
-        for row in stmt.query(&[]).unwrap() {
+        for row in stmt.query(&[]).unwrap().iter() {
             let table: String = row.get("table");
             let schema: String = row.get("schema");
             let is_view: bool = row.get("is_view");
@@ -694,7 +1305,7 @@ impl DatabaseDev for Postgres {
         let conn = self.get_connection();
         let stmt = conn.prepare(&sql).unwrap();
         let mut inherited_columns = Vec::new();
-        for row in stmt.query(&[&schema, &table]).unwrap() {
+        for row in stmt.query(&[&schema, &table]).unwrap().iter() {
             let column: String = row.get("column_parent_name");
             inherited_columns.push(column);
         }
@@ -703,90 +1314,14 @@ impl DatabaseDev for Postgres {
 
 
     /// get the rust data type names from database data type names
-    /// will be used in source code generation
+    /// will be used in source code generation,
+    /// types without an equivalent such as money, xml, bit and enums are retrieved as String
     fn dbtype_to_rust_type(&self, db_type: &str) -> (Vec<String>, Type) {
-        match db_type {
-            "boolean" => {
-                (vec![], Type::Bool)
-            }
-            "char" => {
-                (vec![], Type::I8)
-            }
-            "smallint" | "smallserial" => {
-                (vec![], Type::I16)
-            }
-            "integer" | "int" | "serial" => {
-                (vec![], Type::I32)
-            }
-            "oid" => {
-                (vec![], Type::U32)
-            }
-            "bigint" | "bigserial" => {
-                (vec![], Type::I64)
-            }
-            "real" => {
-                (vec![], Type::F32)
-            }
-            "numeric" => {
-                (vec![], Type::F64)
-                //panic!("No support for numeric yet, please use real or double precision")
-            }
-            "double precision" => {
-                (vec![], Type::F64)
-            }
-            "name" | "character" | "character varying" | "text" | "citext" | "bpchar" => {
-                (vec![], Type::String)
-            }
-            "bytea" => {
-                (vec![], Type::VecU8)
-            }
-            "json" | "jsonb" => {
-                (vec!["rustc_serialize::json::Json".to_owned()], Type::Json)
-            }
-            "uuid" => {
-                (vec!["uuid::Uuid".to_owned()], Type::Uuid)
-            }
-            "timestamp" => {
-                (vec!["chrono::naive::datetime::NaiveDateTime".to_owned()],
-                 Type::NaiveDateTime)
-            }
-            "timestamp without time zone" => {
-                (vec!["chrono::naive::datetime::NaiveDateTime".to_owned()],
-                 Type::NaiveDateTime)
-            }
-            "timestamp with time zone" => {
-                (vec!["chrono::datetime::DateTime".to_owned(),
-                      "chrono::offset::utc::UTC".to_owned()],
-                 Type::DateTime)
-            }
-            "time with time zone" => {
-                (vec!["chrono::naive::time::NaiveTime".to_owned(),
-                      "chrono::offset::utc::UTC".to_owned()],
-                 Type::NaiveTime)
-            }
-            "date" => {
-                (vec!["chrono::naive::date::NaiveDate".to_owned()],
-                 Type::NaiveDate)
-            }
-            "time" => {
-                (vec!["chrono::naive::time::NaiveTime".to_owned()],
-                 Type::NaiveTime)
-            }
-            "hstore" => {
-                (vec!["std::collections::HashMap".to_owned()],
-                 Type::Object)
-            }
-            "interval" => {
-                (vec![], Type::U32)
-            }
-            "inet[]" => {
-                (vec![], Type::String)
-            }
-            "tsvector" | "inet" => {
-                (vec![], Type::String)
-            }//or everything else should be string
-            _ => panic!("Unable to get the equivalent data type for {}", db_type),
-        }
+        self.equivalent_rust_type(db_type).unwrap_or((vec![], Type::String))
+    }
+
+    fn has_rust_type(&self, db_type: &str) -> bool {
+        self.equivalent_rust_type(db_type).is_some()
     }
 
     ///
@@ -801,10 +1336,10 @@ impl DatabaseDev for Postgres {
             Type::I8 => {
                 "char".to_owned()
             }
-            Type::I16 => {
+            Type::I16 | Type::U8 => {
                 "smallint".to_owned()
             }
-            Type::I32 => {
+            Type::I32 | Type::U16 => {
                 "integer".to_owned()
             }
             Type::U32 => {
@@ -813,6 +1348,9 @@ impl DatabaseDev for Postgres {
             Type::I64 => {
                 "bigint".to_owned()
             }
+            Type::U64 => {
+                "numeric(20)".to_owned()
+            }
             Type::F32 => {
                 "real".to_owned()
             }
@@ -1,49 +1,287 @@
 use query::Query;
-use dao::Dao;
+use dao::{Dao, ColumnInfo, Rows};
 
 use dao::Value;
 use database::{Database, DatabaseDev, BuildMode};
 use writer::SqlFrag;
 use database::SqlOption;
 use rusqlite::SqliteConnection;
-use rusqlite::types::ToSql;
+use rusqlite::types::{ToSql, Null};
+use rustc_serialize::json::ToJson;
 use rusqlite::SqliteRow;
+use rusqlite::{SqliteStatement, SqliteRows};
 use table::{Table, Column, Foreign};
 use database::DatabaseDDL;
-use database::DbError;
+use database::{DbError, DaoStream, StreamStatement};
 use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
 use regex::Regex;
 use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 use dao::Type;
+use dao::{DaoResult, FromValue};
+use query::source::QuerySource;
+use uuid::Uuid;
+use chrono::datetime::DateTime;
+use chrono::offset::utc::UTC;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::naive::date::NaiveDate;
+use chrono::naive::time::NaiveTime;
+use rustc_serialize::json::Json;
 use query::Operand;
+use transaction::savepoint_name;
+use std::cell::Cell;
+use rusqlite::SqliteError;
+use database::ErrorKind;
+use database::default_sql;
 
 pub struct Sqlite {
     pool: Option<PooledConnection<SqliteConnectionManager>>,
+    /// the number of nested transactions currently active on this connection
+    transaction_depth: Cell<usize>,
+    /// the major and minor version of the sqlite library, read once when first needed
+    version_number: Cell<Option<(u32, u32)>>,
+}
+
+/// a statement prepared for its rows to be streamed, with the names and declared types of its columns
+pub struct SqliteStreamStatement<'a> {
+    sqlite: &'a Sqlite,
+    stmt: SqliteStatement<'a>,
+    params: Vec<Value>,
+    columns: Vec<String>,
+    declared: BTreeMap<String, (String, Option<String>)>,
+}
+
+impl <'a>StreamStatement for SqliteStreamStatement<'a> {
+    fn rows<'s>(&'s mut self) -> Result<DaoStream<'s>, DbError> {
+        let param = self.sqlite.from_rust_type_tosql(&self.params);
+        let param: Vec<&ToSql> = param.iter().map(|p| &**p).collect();
+        let rows = try!(self.stmt.query(&param));
+        Ok(Box::new(SqliteStream {
+            rows: rows,
+            sqlite: self.sqlite,
+            columns: &self.columns,
+            declared: &self.declared,
+            done: false,
+        }))
+    }
+}
+
+/// the rows of a statement, decoded as sqlite steps through them
+pub struct SqliteStream<'s> {
+    rows: SqliteRows<'s>,
+    sqlite: &'s Sqlite,
+    columns: &'s [String],
+    declared: &'s BTreeMap<String, (String, Option<String>)>,
+    /// sqlite restarts a statement when it is stepped again after its last row
+    done: bool,
+}
+
+impl <'s>Iterator for SqliteStream<'s> {
+    type Item = Result<Dao, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let row = match self.rows.next() {
+            Some(Ok(row)) => row,
+            Some(Err(e)) => return Some(Err(DbError::from(e))),
+            None => {
+                self.done = true;
+                return None;
+            }
+        };
+        let mut dao = Dao::new();
+        for (index, col) in self.columns.iter().enumerate() {
+            let hint = self.declared.get(col).map(|&(ref d, _)| &**d);
+            let rtype = self.sqlite.from_sql_to_rust_type(&row, index, hint);
+            dao.set_value(col, rtype);
+        }
+        Some(Ok(dao))
+    }
 }
 
 impl Sqlite {
     pub fn new() -> Self {
-        Sqlite { pool: None }
+        Sqlite {
+            pool: None,
+            transaction_depth: Cell::new(0),
+            version_number: Cell::new(None),
+        }
     }
 
     pub fn with_pooled_connection(pool: PooledConnection<SqliteConnectionManager>) -> Self {
-        Sqlite { pool: Some(pool) }
+        Sqlite {
+            pool: Some(pool),
+            transaction_depth: Cell::new(0),
+            version_number: Cell::new(None),
+        }
     }
 
-    fn from_rust_type_tosql<'a>(&self, types: &'a [Value]) -> Vec<&'a ToSql> {
-        let mut params: Vec<&ToSql> = vec![];
-        for t in types {
-            match t {
-                &Value::String(ref x) => {
-                    params.push(x);
+    /// whether the sqlite library of the connection is at least this version,
+    /// features are not assumed when there is no connection to check the version
+    fn is_version_at_least(&self, major: u32, minor: u32) -> bool {
+        let version = match self.version_number.get() {
+            Some(version) => version,
+            None => {
+                if self.pool.is_none() {
+                    return false;
                 }
-                _ => panic!("not yet here {:?}", t),
+                let version = match self.version() {
+                    Ok(version) => {
+                        let mut parts = version.split('.').map(|p| p.parse::<u32>().unwrap_or(0));
+                        (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+                    }
+                    Err(_) => (0, 0),
+                };
+                self.version_number.set(Some(version));
+                version
             }
+        };
+        version >= (major, minor)
+    }
+
+    /// convert the values into their sqlite storage representation,
+    /// types which sqlite lacks natively are stored as:
+    ///
+    ///  * bool - INTEGER 0 or 1
+    ///  * unsigned integers - INTEGER, u64 values beyond i64 range are stored as TEXT
+    ///  * f32 - REAL
+    ///  * Uuid - TEXT in the hyphenated form
+    ///  * DateTime - TEXT in RFC 3339, ie: 2016-02-29T10:30:00+00:00
+    ///  * NaiveDateTime - TEXT, ie: 2016-02-29 10:30:00
+    ///  * NaiveDate - TEXT, ie: 2016-02-29
+    ///  * NaiveTime - TEXT, ie: 10:30:00
+    ///  * Json and Object - TEXT of the serialized json
+    ///  * None of any type - NULL
+    fn from_rust_type_tosql(&self, types: &[Value]) -> Vec<Box<ToSql>> {
+        let mut params: Vec<Box<ToSql>> = vec![];
+        for t in types {
+            let param: Box<ToSql> = match *t {
+                Value::Bool(x) => Box::new(x),
+                Value::I8(x) => Box::new(x as i64),
+                Value::I16(x) => Box::new(x as i64),
+                Value::I32(x) => Box::new(x as i64),
+                Value::I64(x) => Box::new(x),
+                Value::U8(x) => Box::new(x as i64),
+                Value::U16(x) => Box::new(x as i64),
+                Value::U32(x) => Box::new(x as i64),
+                Value::U64(x) => {
+                    if x <= i64::max_value() as u64 {
+                        Box::new(x as i64)
+                    } else {
+                        Box::new(x.to_string())
+                    }
+                }
+                Value::F32(x) => Box::new(x as f64),
+                Value::F64(x) => Box::new(x),
+                Value::String(ref x) => Box::new(x.to_owned()),
+                Value::VecU8(ref x) => Box::new(x.clone()),
+                Value::Uuid(ref x) => Box::new(x.hyphenated().to_string()),
+                Value::DateTime(ref x) => Box::new(x.to_rfc3339()),
+                Value::NaiveDateTime(ref x) => Box::new(x.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+                Value::NaiveDate(ref x) => Box::new(x.format("%Y-%m-%d").to_string()),
+                Value::NaiveTime(ref x) => Box::new(x.format("%H:%M:%S%.f").to_string()),
+                Value::Json(ref x) => Box::new(x.to_string()),
+                Value::Object(_) => Box::new(t.to_json().to_string()),
+                Value::None(_) => Box::new(Null),
+            };
+            params.push(param);
         }
         params
     }
 
+    /// the column name, type, NOT NULL and DEFAULT used in CREATE TABLE and ADD COLUMN,
+    /// defaults are enclosed in parenthesis so function calls are accepted
+    fn column_definition(&self, column: &Column) -> String {
+        let mut definition = format!("{} {}", column.name, self.rust_type_to_dbtype(&column.data_type));
+        if column.not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(ref default) = column.default {
+            if let Some(default) = default_sql(default) {
+                definition.push_str(&format!(" DEFAULT ({})", default));
+            }
+        }
+        definition
+    }
+
+    /// the statements which recreate the table with the definition of the model,
+    /// following the procedure in https://www.sqlite.org/lang_altertable.html
+    /// The records of the columns in the model are copied over to the new table.
+    /// Indexes and triggers of the old table are not recreated.
+    /// Dropping the old table deletes its records first, which cascades to or fails on the referring records
+    /// unless the foreign keys are switched off, which can only be done outside of a transaction
+    fn build_rebuild_table(&self, model: &Table) -> SqlFrag {
+        let mut new_table = model.clone();
+        new_table.name = format!("rustorm_new_{}", model.name);
+        let columns: Vec<String> = model.columns.iter().map(|c| c.name.to_owned()).collect();
+        let columns = columns.join(", ");
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("-- execute outside of a transaction after PRAGMA foreign_keys = OFF,");
+        w.ln();
+        w.append("-- dropping the table deletes the records referring to it otherwise");
+        w.ln();
+        w.append(&self.build_create_table(&new_table, false).sql);
+        w.append(";");
+        w.ln();
+        w.append(&format!("INSERT INTO {} ({}) SELECT {} FROM {};", new_table.name, columns, columns, model.name));
+        w.ln();
+        w.append(&format!("DROP TABLE {};", model.name));
+        w.ln();
+        w.append(&format!("ALTER TABLE {} RENAME TO {};", new_table.name, model.name));
+        w
+    }
+
+    /// execute the statements rebuilding the table inside a savepoint,
+    /// with the foreign keys enforcement switched off while the tables are swapped.
+    /// The changes are rolled back when the existing records violate the foreign keys.
+    /// The foreign keys can not be switched off inside a transaction,
+    /// the table is not rebuilt when they are enforced in a transaction
+    fn rebuild_table(&self, model: &Table, frag: &SqlFrag) -> Result<(), DbError> {
+        let conn = self.get_connection();
+        let foreign_keys: Vec<Dao> = try!(self.execute_sql_with_return("PRAGMA foreign_keys", &[]));
+        let enforced = foreign_keys.get(0).map(|d| d.get_opt::<i64>("foreign_keys") == Some(1)).unwrap_or(false);
+        if enforced && self.is_transacted() {
+            return Err(DbError::new(&format!("The table {} can not be rebuilt in a transaction while the foreign keys are enforced, \
+                                              dropping it would delete the records referring to it. \
+                                              Execute PRAGMA foreign_keys = OFF before the transaction begins",
+                                             model.name)));
+        }
+        if enforced {
+            try!(conn.execute_batch("PRAGMA foreign_keys = OFF"));
+        }
+        let result = self.rebuild_in_savepoint(model, frag);
+        if enforced {
+            try!(conn.execute_batch("PRAGMA foreign_keys = ON"));
+        }
+        result
+    }
+
+    fn rebuild_in_savepoint(&self, model: &Table, frag: &SqlFrag) -> Result<(), DbError> {
+        let conn = self.get_connection();
+        try!(conn.execute_batch("SAVEPOINT rustorm_rebuild"));
+        let rebuilt = match conn.execute_batch(&frag.sql) {
+            Ok(_) => self.execute_sql_with_return(&format!("PRAGMA foreign_key_check({})", model.name), &[]),
+            Err(e) => Err(DbError::from(e)),
+        };
+        match rebuilt {
+            Ok(ref violations) if violations.is_empty() => {
+                try!(conn.execute_batch("RELEASE rustorm_rebuild"));
+                Ok(())
+            }
+            Ok(_) => {
+                try!(conn.execute_batch("ROLLBACK TO rustorm_rebuild; RELEASE rustorm_rebuild"));
+                Err(DbError::new(&format!("Records of {} violate the foreign key constraints", model.name)))
+            }
+            Err(e) => {
+                try!(conn.execute_batch("ROLLBACK TO rustorm_rebuild; RELEASE rustorm_rebuild"));
+                Err(e)
+            }
+        }
+    }
+
     pub fn get_connection(&self) -> &SqliteConnection {
         match self.pool {
             Some(ref pool) => &pool,
@@ -51,15 +289,174 @@ impl Sqlite {
         }
     }
 
-    /// convert a record of a row into rust type
-    fn from_sql_to_rust_type(&self, row: &SqliteRow, index: usize) -> Value {
-        let value = row.get_checked(index as i32);
-        match value {
-            Ok(value) => Value::String(value),
-            Err(_) => Value::None(Type::String),
+    /// convert a record of a row into rust type,
+    /// the value is read according to its storage class then converted
+    /// to the type of the declared column type when one is known.
+    /// Values which can not be converted to the declared type are kept as stored
+    fn from_sql_to_rust_type(&self, row: &SqliteRow, index: usize, declared: Option<&str>) -> Value {
+        let index = index as i32;
+        let declared_type = declared.map(|d| self.dbtype_to_rust_type(d).1);
+        let stored = if let Ok(value) = row.get_checked::<Option<i64>>(index) {
+            match value {
+                Some(value) => Value::I64(value),
+                None => return Value::None(declared_type.unwrap_or(Type::String)),
+            }
+        } else if let Ok(value) = row.get_checked::<f64>(index) {
+            Value::F64(value)
+        } else if let Ok(value) = row.get_checked::<String>(index) {
+            Value::String(value)
+        } else if let Ok(value) = row.get_checked::<Vec<u8>>(index) {
+            Value::VecU8(value)
+        } else {
+            return Value::None(declared_type.unwrap_or(Type::String));
+        };
+        match declared_type {
+            Some(declared_type) => Sqlite::convert_stored(stored, &declared_type),
+            None => stored,
         }
     }
 
+    /// convert the stored value to the declared type,
+    /// keep the stored value when it is not representable in the declared type
+    fn convert_stored(stored: Value, declared_type: &Type) -> Value {
+        let converted = match *declared_type {
+            Type::Bool => bool::try_from_type(stored.clone()).map(Value::Bool).ok(),
+            Type::I64 => i64::try_from_type(stored.clone()).map(Value::I64).ok(),
+            Type::F64 => f64::try_from_type(stored.clone()).map(Value::F64).ok(),
+            Type::Uuid => Uuid::try_from_type(stored.clone()).map(Value::Uuid).ok(),
+            Type::DateTime => DateTime::<UTC>::try_from_type(stored.clone()).map(Value::DateTime).ok(),
+            Type::NaiveDateTime => NaiveDateTime::try_from_type(stored.clone()).map(Value::NaiveDateTime).ok(),
+            Type::NaiveDate => NaiveDate::try_from_type(stored.clone()).map(Value::NaiveDate).ok(),
+            Type::NaiveTime => NaiveTime::try_from_type(stored.clone()).map(Value::NaiveTime).ok(),
+            Type::Json => {
+                match stored {
+                    Value::String(ref x) => Json::from_str(x).map(Value::Json).ok(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        converted.unwrap_or(stored)
+    }
+
+    /// the declared types of the columns of the tables the query selects from,
+    /// used as hints when decoding the result.
+    /// The table of a column is only known when no other queried table has a column with the same name
+    fn declared_types(&self, query: &Query) -> BTreeMap<String, (String, Option<String>)> {
+        let mut declared = BTreeMap::new();
+        let mut tables = vec![];
+        for source in &query.from {
+            if let QuerySource::TableName(ref table_name) = source.source {
+                tables.push(table_name.name.to_owned());
+            }
+        }
+        for join in &query.joins {
+            tables.push(join.table_name.name.to_owned());
+        }
+        for table in tables {
+            let sql = format!("PRAGMA table_info({});", table);
+            if let Ok(result) = self.execute_sql_with_hints(&sql, &[], &BTreeMap::new()) {
+                for r in result.to_daos() {
+                    let column: Option<String> = r.get_opt("name");
+                    let db_data_type: Option<String> = r.get_opt("type");
+                    if let (Some(column), Some(db_data_type)) = (column, db_data_type) {
+                        match declared.entry(column) {
+                            Entry::Vacant(entry) => {
+                                entry.insert((db_data_type, Some(table.to_owned())));
+                            }
+                            Entry::Occupied(mut entry) => {
+                                entry.get_mut().1 = None;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        declared
+    }
+
+    /// execute the sql, decoding the columns with the declared types when known.
+    /// The type of a column without a declared type is the storage class of its first non null value
+    fn execute_sql_with_hints(&self,
+                              sql: &str,
+                              params: &[Value],
+                              declared: &BTreeMap<String, (String, Option<String>)>)
+                              -> Result<Rows, DbError> {
+        println!("SQL: \n{}", sql);
+        println!("param: {:?}", params);
+        let conn = self.get_connection();
+        let mut stmt = try!(conn.prepare(sql));
+        let param = self.from_rust_type_tosql(params);
+        let param: Vec<&ToSql> = param.iter().map(|p| &**p).collect();
+        let mut columns = vec![];
+        for c in stmt.column_names() {
+            let column = match declared.get(c) {
+                Some(&(ref db_type, ref table)) => {
+                    ColumnInfo {
+                        name: c.to_owned(),
+                        table: table.clone(),
+                        db_type: db_type.to_owned(),
+                        data_type: self.dbtype_to_rust_type(db_type).1,
+                    }
+                }
+                None => {
+                    ColumnInfo {
+                        name: c.to_owned(),
+                        table: None,
+                        db_type: "".to_owned(),
+                        data_type: Type::String,
+                    }
+                }
+            };
+            columns.push(column);
+        }
+        println!("columns : {:?}", columns);
+        let mut result = Rows::new(columns);
+        let mut undeclared: Vec<bool> = result.columns.iter().map(|c| !declared.contains_key(&c.name)).collect();
+        let rows = try!(stmt.query(&param));
+        for row in rows {
+            let row = try!(row);
+            let mut values = vec![];
+            for (index, column) in result.columns.iter_mut().enumerate() {
+                let hint = declared.get(&column.name).map(|&(ref d, _)| &**d);
+                let rtype = self.from_sql_to_rust_type(&row, index, hint);
+                if undeclared[index] {
+                    match rtype {
+                        Value::None(_) => (),
+                        _ => {
+                            column.data_type = rtype.get_type();
+                            undeclared[index] = false;
+                        }
+                    }
+                }
+                values.push(rtype);
+            }
+            result.push(values);
+        }
+        Ok(result)
+    }
+
+    /// prepare the sql for sqlite to step through its rows,
+    /// decoding the columns with the declared types when known
+    fn stream_sql_with_hints<'a>(&'a self,
+                                 sql: &str,
+                                 params: &[Value],
+                                 declared: BTreeMap<String, (String, Option<String>)>)
+                                 -> Result<Box<StreamStatement + 'a>, DbError> {
+        println!("SQL: \n{}", sql);
+        println!("param: {:?}", params);
+        let conn = self.get_connection();
+        let stmt = try!(conn.prepare(sql));
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| (*c).to_owned()).collect();
+        Ok(Box::new(SqliteStreamStatement {
+            sqlite: self,
+            stmt: stmt,
+            params: params.to_vec(),
+            columns: columns,
+            declared: declared,
+        }))
+    }
+
     ///
     /// convert rust data type names to database data type names
     /// will be used in generating SQL for table creation
@@ -70,7 +467,7 @@ impl Sqlite {
             Type::Bool => {
                 "boolean".to_owned()
             }
-           Type::I8 => {
+           Type::I8 | Type::U8 | Type::U16 | Type::U64 => {
                 "integer".to_owned()
             }
             Type::I16 => {
@@ -98,22 +495,25 @@ impl Sqlite {
                 "blob".to_owned()
             }
             Type::Json => {
-                "text".to_owned()
+                "json".to_owned()
+            }
+            Type::Object => {
+                "json".to_owned()
             }
             Type::Uuid => {
-                "text".to_owned()
+                "uuid".to_owned()
             }
             Type::NaiveDateTime => {
-                "numeric".to_owned()
+                "timestamp".to_owned()
             }
             Type::DateTime => {
-                "numeric".to_owned()
+                "timestamp with time zone".to_owned()
             }
             Type::NaiveDate => {
-                "numeric".to_owned()
+                "date".to_owned()
             }
             Type::NaiveTime => {
-                "numeric".to_owned()
+                "time".to_owned()
             }
             _ => panic!("Unable to get the equivalent database data type for {:?}",
                         rust_type),
@@ -272,6 +672,91 @@ impl Sqlite {
     }
 }
 
+/// the kind of error from the SQLite result code and message
+pub fn error_kind(err: &SqliteError) -> ErrorKind {
+    failure_info(err).0
+}
+
+/// the name of the violated constraint,
+/// SQLite only reports it for named CHECK constraints
+pub fn error_constraint(err: &SqliteError) -> Option<String> {
+    failure_info(err).1
+}
+
+/// the name of the table involved in the error, extracted from the error message
+pub fn error_table(err: &SqliteError) -> Option<String> {
+    failure_info(err).2
+}
+
+/// the table part of `table.column` in the message
+fn table_of(columns: &str) -> Option<String> {
+    columns.split(',')
+           .next()
+           .and_then(|column| {
+               let column = column.trim();
+               column.rfind('.').map(|dot| column[..dot].to_owned())
+           })
+}
+
+/// the kind, constraint and table of a failed SQLite call,
+/// errors raised by the driver itself are of no particular kind
+fn failure_info(err: &SqliteError) -> (ErrorKind, Option<String>, Option<String>) {
+    match *err {
+        SqliteError::SqliteFailure(ref failure, ref message) => {
+            error_info(failure.extended_code, message.as_ref().map(|m| &m[..]).unwrap_or(""))
+        }
+        _ => (ErrorKind::Other, None, None),
+    }
+}
+
+/// the kind, constraint and table of the error,
+/// the extended result codes are used when enabled, otherwise the message is inspected
+fn error_info(code: i32, message: &str) -> (ErrorKind, Option<String>, Option<String>) {
+    match code {
+        // SQLITE_CONSTRAINT and its extended codes
+        19 | 275 | 787 | 1299 | 1555 | 2067 => {
+            if message.starts_with("UNIQUE constraint failed: ") {
+                let columns = &message["UNIQUE constraint failed: ".len()..];
+                (ErrorKind::UniqueViolation, None, table_of(columns))
+            } else if message.starts_with("NOT NULL constraint failed: ") {
+                let columns = &message["NOT NULL constraint failed: ".len()..];
+                (ErrorKind::NotNullViolation, None, table_of(columns))
+            } else if message.starts_with("CHECK constraint failed: ") {
+                let name = &message["CHECK constraint failed: ".len()..];
+                (ErrorKind::CheckViolation, Some(name.trim().to_owned()), None)
+            } else if message.starts_with("FOREIGN KEY constraint failed") {
+                (ErrorKind::ForeignKeyViolation, None, None)
+            } else {
+                match code {
+                    275 => (ErrorKind::CheckViolation, None, None),
+                    787 => (ErrorKind::ForeignKeyViolation, None, None),
+                    1299 => (ErrorKind::NotNullViolation, None, None),
+                    1555 | 2067 => (ErrorKind::UniqueViolation, None, None),
+                    _ => (ErrorKind::Other, None, None),
+                }
+            }
+        }
+        // SQLITE_BUSY_SNAPSHOT, the snapshot of the read transaction is stale
+        517 => (ErrorKind::SerializationFailure, None, None),
+        // SQLITE_BUSY, SQLITE_LOCKED and their extended codes, the busy timeout has elapsed
+        _ if code & 0xff == 5 || code & 0xff == 6 => (ErrorKind::LockTimeout, None, None),
+        // SQLITE_IOERR, SQLITE_CANTOPEN
+        _ if code & 0xff == 10 || code & 0xff == 14 => (ErrorKind::ConnectionLost, None, None),
+        _ => {
+            if message.starts_with("no such table: ") {
+                let table = &message["no such table: ".len()..];
+                (ErrorKind::UndefinedTable, None, Some(table.trim().to_owned()))
+            } else if message.starts_with("no such column: ") {
+                (ErrorKind::UndefinedColumn, None, None)
+            } else if message.contains("syntax error") {
+                (ErrorKind::SyntaxError, None, None)
+            } else {
+                (ErrorKind::Other, None, None)
+            }
+        }
+    }
+}
+
 impl Database for Sqlite {
     fn version(&self) -> Result<String, DbError> {
         let sql = "SELECT sqlite_version() AS version";
@@ -281,17 +766,47 @@ impl Database for Sqlite {
             None => Err(DbError::new("Unable to get database version")),
         }
     }
-    fn begin(&self) {
-        unimplemented!()
+    fn begin(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            try!(self.execute_sql("BEGIN TRANSACTION", &[]));
+        } else {
+            try!(self.execute_sql(&format!("SAVEPOINT {}", savepoint_name(depth)), &[]));
+        }
+        self.transaction_depth.set(depth + 1);
+        Ok(())
     }
-    fn commit(&self) {
-        unimplemented!()
+    fn commit(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to commit, there is no active transaction"));
+        }
+        // the transaction is still open when the commit fails, ie: a deferred constraint is violated
+        if depth == 1 {
+            try!(self.execute_sql("COMMIT", &[]));
+        } else {
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint_name(depth - 1)), &[]));
+        }
+        self.transaction_depth.set(depth - 1);
+        Ok(())
     }
-    fn rollback(&self) {
-        unimplemented!()
+    fn rollback(&self) -> Result<(), DbError> {
+        let depth = self.transaction_depth.get();
+        if depth == 0 {
+            return Err(DbError::new("Unable to rollback, there is no active transaction"));
+        }
+        self.transaction_depth.set(depth - 1);
+        if depth == 1 {
+            try!(self.execute_sql("ROLLBACK", &[]));
+        } else {
+            let savepoint = savepoint_name(depth - 1);
+            try!(self.execute_sql(&format!("ROLLBACK TO SAVEPOINT {}", savepoint), &[]));
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", savepoint), &[]));
+        }
+        Ok(())
     }
     fn is_transacted(&self) -> bool {
-        false
+        self.transaction_depth.get() > 0
     }
     fn is_closed(&self) -> bool {
         false
@@ -311,14 +826,30 @@ impl Database for Sqlite {
 
     /// return this list of options, supported features in the database
     fn sql_options(&self) -> Vec<SqlOption> {
-        vec![
+        let mut options = vec![
             SqlOption::UsesNumberedParam,  // uses numbered parameters
             SqlOption::SupportsCTE,
-        ]
+            SqlOption::SupportsInsertOrReplace,
+            SqlOption::SupportsIntersectExcept,
+            SqlOption::SupportsTransactionalDDL,
+        ];
+        // the ON CONFLICT clause of an insert is available since sqlite 3.24
+        if self.is_version_at_least(3, 24) {
+            options.push(SqlOption::SupportsOnConflict);
+        }
+        if self.is_version_at_least(3, 25) {
+            options.push(SqlOption::SupportsWindowFunctions);
+        }
+        options
+    }
+
+    /// SQLITE_MAX_VARIABLE_NUMBER defaults to 999
+    fn max_parameters(&self) -> usize {
+        999
     }
 
     fn insert(&self, query: &Query) -> Result<Dao, DbError> {
-        let sql_frag = self.build_insert(query, BuildMode::Standard);
+        let sql_frag = try!(self.build_insert(query, BuildMode::Standard));
         match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
             Ok(Some(result)) => Ok(result),
             Ok(None) => Err(DbError::new("No result from insert")),
@@ -332,36 +863,48 @@ impl Database for Sqlite {
         unimplemented!()
     }
 
-    /// sqlite does not return the columns mentioned in the query,
-    /// you have to specify it yourself
-    /// TODO: found this
-    /// http://jgallagher.github.io/rusqlite/rusqlite/struct.SqliteStatement.html#method.column_names
-    fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError> {
-        println!("SQL: \n{}", sql);
-        println!("param: {:?}", params);
-        let conn = self.get_connection();
-        let mut stmt = conn.prepare(sql).unwrap();
-        let mut daos = vec![];
-        let param = self.from_rust_type_tosql(params);
+    /// execute the query, using the declared column types of the queried tables
+    /// to decode the values sqlite stores without a native type, ie: bool, uuid, timestamps
+    fn execute_with_return(&self, query: &Query) -> Result<DaoResult, DbError> {
+        let declared = self.declared_types(query);
         let mut columns = vec![];
-        for c in stmt.column_names() {
-            columns.push(c.to_owned());
+        let mut rows = vec![];
+        for batch in try!(self.batches(query, self.max_parameters())) {
+            let sql_frag = &try!(self.build_query(&batch, BuildMode::Standard));
+            let mut batch_result = try!(self.execute_sql_with_hints(&sql_frag.sql, &sql_frag.params, &declared));
+            columns = batch_result.columns;
+            rows.append(&mut batch_result.values);
         }
-        println!("columns : {:?}", columns);
-        let rows = try!(stmt.query(&param));
-        for row in rows {
-            let row = try!(row);
-            let mut index = 0;
-            let mut dao = Dao::new();
-            for col in &columns {
-                let rtype = self.from_sql_to_rust_type(&row, index);
-                println!("{:?}", rtype);
-                dao.set_value(col, rtype);
-                index += 1;
-            }
-            daos.push(dao);
+        let result = Rows {
+            columns: columns,
+            values: rows,
+        };
+        let dao_result = DaoResult {
+            columns: result.columns,
+            rows: result.values,
+            renamed_columns: query.get_renamed_columns(),
+            total: None,
+            page: None,
+            page_size: None,
+        };
+        Ok(dao_result)
+    }
+
+    fn execute_with_one_return(&self, query: &Query) -> Result<Option<Dao>, DbError> {
+        let declared = self.declared_types(query);
+        let sql_frag = &try!(self.build_query(query, BuildMode::Standard));
+        let mut dao = try!(self.execute_sql_with_hints(&sql_frag.sql, &sql_frag.params, &declared)).to_daos();
+        if dao.len() >= 1 {
+            Ok(Some(dao.remove(0)))
+        } else {
+            Ok(None)
         }
-        Ok(daos)
+    }
+
+    /// the values are decoded by their storage class only,
+    /// the column types of an arbitrary sql are not known
+    fn execute_sql_with_columns(&self, sql: &str, params: &[Value]) -> Result<Rows, DbError> {
+        self.execute_sql_with_hints(sql, params, &BTreeMap::new())
     }
 
 
@@ -377,6 +920,26 @@ impl Database for Sqlite {
         }
     }
 
+    fn execute_batch(&self, sql: &str) -> Result<(), DbError> {
+        println!("SQL: \n{}", sql);
+        let conn = self.get_connection();
+        try!(conn.execute_batch(sql));
+        Ok(())
+    }
+
+    /// the values are decoded by their storage class only,
+    /// the column types of an arbitrary sql are not known
+    fn stream_sql<'a>(&'a self, sql: &str, params: &[Value]) -> Result<Box<StreamStatement + 'a>, DbError> {
+        self.stream_sql_with_hints(sql, params, BTreeMap::new())
+    }
+
+    /// the declared types of the tables of the query are used when decoding the values
+    fn stream<'a>(&'a self, query: &Query) -> Result<Box<StreamStatement + 'a>, DbError> {
+        let declared = self.declared_types(query);
+        let sql_frag = try!(self.build_query(query, BuildMode::Standard));
+        self.stream_sql_with_hints(&sql_frag.sql, &sql_frag.params, declared)
+    }
+
     /// generic execute sql which returns not much information,
     /// returns only the number of affected records or errors
     /// can be used with DDL operations (CREATE, DELETE, ALTER, DROP)
@@ -384,6 +947,7 @@ impl Database for Sqlite {
         println!("SQL: \n{}", sql);
         println!("param: {:?}", params);
         let to_sql_types = self.from_rust_type_tosql(params);
+        let to_sql_types: Vec<&ToSql> = to_sql_types.iter().map(|p| &**p).collect();
         let conn = self.get_connection();
         let result = conn.execute(sql, &to_sql_types);
         match result {
@@ -399,80 +963,163 @@ impl Database for Sqlite {
 }
 
 impl DatabaseDDL for Sqlite {
-    fn create_schema(&self, _schema: &str) {
-        panic!("sqlite does not support schema")
+
+    /// sqlite has no schema, this does nothing
+    fn create_schema(&self, _schema: &str, _if_not_exists: bool) -> Result<(), DbError> {
+        Ok(())
     }
 
-    fn drop_schema(&self, _schema: &str) {
-        panic!("sqlite does not support schema")
+    /// sqlite has no schema, the sql is empty
+    fn build_create_schema(&self, _schema: &str, _if_not_exists: bool) -> SqlFrag {
+        SqlFrag::new(self.sql_options(), BuildMode::Standard)
     }
 
-    fn build_create_table(&self, table: &Table) -> SqlFrag {
+    /// sqlite has no schema, this does nothing
+    fn drop_schema(&self, _schema: &str, _if_exists: bool) -> Result<(), DbError> {
+        Ok(())
+    }
 
+    /// sqlite has no schema, the sql is empty
+    fn build_drop_schema(&self, _schema: &str, _if_exists: bool) -> SqlFrag {
+        SqlFrag::new(self.sql_options(), BuildMode::Standard)
+    }
 
+    /// the schema of the table is ignored since sqlite has no schema
+    fn build_create_table(&self, table: &Table, if_not_exists: bool) -> SqlFrag {
         let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
         w.append("CREATE TABLE ");
+        if if_not_exists {
+            w.append("IF NOT EXISTS ");
+        }
         w.append(&table.name);
-        w.append("(");
-        w.ln_tab();
+        w.append(" (");
         let mut do_comma = false;
         for c in &table.columns {
             if do_comma {
-                w.commasp();
-                w.ln_tab();
+                w.comma();
             } else {
                 do_comma = true;
             }
-            w.append(&c.name);
-            w.append(" ");
-            let dt = self.rust_type_to_dbtype(&c.data_type);
-            w.append(&dt);
-            if c.is_primary {
-                w.append(" PRIMARY KEY ");
-            }
-        }
-		let mut do_comma = true;//there has been colcommentsumns mentioned
-		for c in &table.columns {
-			if let Some(ref foreign) = c.foreign {
-				if do_comma {
-					w.commasp();
-				} else {
-					do_comma = true;
-				}
-				w.ln_tab();
-				w.append("FOREIGN KEY");
-				w.append(&format!("({})", c.name));
-				w.append(" REFERENCES ");
-				w.append(&format!("{}", foreign.table));
-				w.append(&format!("({})", foreign.column));
-			}
-		}
+            w.ln_tab();
+            w.append(&self.column_definition(c));
+        }
+        let primary_columns: Vec<String> = table.primary_columns().iter().map(|c| c.name.to_owned()).collect();
+        if !primary_columns.is_empty() {
+            w.comma();
+            w.ln_tab();
+            w.append(&format!("PRIMARY KEY ({})", primary_columns.join(", ")));
+        }
+        for c in &table.columns {
+            if c.is_unique && !c.is_primary {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("UNIQUE ({})", c.name));
+            }
+        }
+        for c in &table.columns {
+            if let Some(ref foreign) = c.foreign {
+                w.comma();
+                w.ln_tab();
+                w.append(&format!("FOREIGN KEY ({}) REFERENCES {} ({})", c.name, foreign.table, foreign.column));
+            }
+        }
         w.ln();
         w.append(")");
         w
     }
-    fn create_table(&self, table: &Table) {
-        let frag = self.build_create_table(table);
-        match self.execute_sql(&frag.sql, &vec![]) {
-            Ok(_) => println!("created table.."),
-            Err(e) => panic!("table not created {}", e),
+
+    fn create_table(&self, table: &Table, if_not_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_create_table(table, if_not_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    fn rename_table(&self, table: &Table, new_tablename: String) -> Result<(), DbError> {
+        let frag = self.build_rename_table(table, &new_tablename);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    fn build_rename_table(&self, table: &Table, new_tablename: &str) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.name);
+        w.append(" RENAME TO ");
+        w.append(new_tablename);
+        w
+    }
+
+    fn drop_table(&self, table: &Table, if_exists: bool) -> Result<(), DbError> {
+        let frag = self.build_drop_table(table, if_exists);
+        try!(self.execute_sql(&frag.sql, &vec![]));
+        Ok(())
+    }
+
+    fn build_drop_table(&self, table: &Table, if_exists: bool) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("DROP TABLE ");
+        if if_exists {
+            w.append("IF EXISTS ");
         }
+        w.append(&table.name);
+        w
     }
 
-    fn rename_table(&self, _table: &Table, _new_tablename: String) {
-        unimplemented!()
+    fn set_foreign_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_foreign_constraint(model);
+        self.rebuild_table(model, &frag)
     }
 
-    fn drop_table(&self, _table: &Table) {
-        unimplemented!()
+    /// sqlite can not add constraints to an existing table,
+    /// the table is rebuilt from the model which contains the foreign keys
+    fn build_foreign_constraint(&self, model: &Table) -> SqlFrag {
+        self.build_rebuild_table(model)
     }
 
-    fn set_foreign_constraint(&self, _model: &Table) {
-        unimplemented!()
+    fn set_primary_constraint(&self, model: &Table) -> Result<(), DbError> {
+        let frag = self.build_primary_constraint(model);
+        self.rebuild_table(model, &frag)
     }
 
-    fn set_primary_constraint(&self, _model: &Table) {
-        unimplemented!()
+    /// sqlite can not add constraints to an existing table,
+    /// the table is rebuilt from the model which contains the primary key
+    fn build_primary_constraint(&self, model: &Table) -> SqlFrag {
+        self.build_rebuild_table(model)
+    }
+
+    /// sqlite only adds columns without primary or unique constraint
+    fn build_add_column(&self, table: &Table, column: &Column) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), BuildMode::Standard);
+        w.append("ALTER TABLE ");
+        w.append(&table.name);
+        w.append(" ADD COLUMN ");
+        w.append(&self.column_definition(column));
+        w
+    }
+
+    /// the table is rebuilt from the table definition, which no longer has the column
+    fn build_drop_column(&self, table: &Table, _column: &Column) -> SqlFrag {
+        self.build_rebuild_table(table)
+    }
+
+    /// the table is rebuilt from the table definition, which has the altered column
+    fn build_alter_column(&self, table: &Table, _column: &Column) -> SqlFrag {
+        self.build_rebuild_table(table)
+    }
+
+    /// the table is rebuilt from the table definition, which has the foreign key
+    fn build_add_foreign_constraint(&self, table: &Table, _column: &Column) -> SqlFrag {
+        self.build_rebuild_table(table)
+    }
+
+    /// the table is rebuilt from the table definition, which no longer has the foreign key
+    fn build_drop_foreign_constraint(&self, table: &Table, _column: &Column) -> SqlFrag {
+        self.build_rebuild_table(table)
+    }
+
+    /// the table is rebuilt from the table definition, which no longer has the primary key
+    fn build_drop_primary_constraint(&self, table: &Table) -> SqlFrag {
+        self.build_rebuild_table(table)
     }
 }
 
@@ -500,14 +1147,14 @@ impl DatabaseDev for Sqlite {
                 for r in result {
                     let column: String = r.get("name");
                     let db_data_type: String = r.get("type");
-                    let default_value: String = r.get("dflt_value");
-                    let not_null: String = r.get("notnull");
-                    let pk: String = r.get("pk");
+                    let default_value: Option<String> = r.get_opt("dflt_value");
+                    let not_null: i64 = r.get("notnull");
+                    let pk: i64 = r.get("pk");
                     println!("column: {}", column);
                     println!("data_type: {}", db_data_type);
                     println!("not null: {}", not_null);
                     println!("pk: {}", pk);
-                    println!("default_value: {}", default_value);
+                    println!("default_value: {:?}", default_value);
 
                     let column_comment = self.get_column_comment(&column_comments, &column);
                     let column_foreign = self.get_column_foreign(&foreign, &column);
@@ -517,11 +1164,11 @@ impl DatabaseDev for Sqlite {
                         name: column,
                         data_type: data_type,
                         db_data_type: db_data_type,
-                        is_primary: pk != "0",
+                        is_primary: pk != 0,
                         is_unique: false,
-                        default: Some(Operand::Value(Value::String(default_value))),
+                        default: default_value.map(|d| Operand::Value(Value::String(d))),
                         comment: column_comment,
-                        not_null: not_null != "0",
+                        not_null: not_null != 0,
                         is_inherited: false,
                         foreign: column_foreign,
                     };
@@ -567,12 +1214,58 @@ impl DatabaseDev for Sqlite {
         vec![]
     }
 
-    fn dbtype_to_rust_type(&self, _db_type: &str) -> (Vec<String>, Type) {
-        unimplemented!()
+    /// the declared types are free form in sqlite,
+    /// the type is matched by name, falling back to the sqlite type affinity rules
+    fn dbtype_to_rust_type(&self, db_type: &str) -> (Vec<String>, Type) {
+        let db_type = db_type.trim().to_lowercase();
+        let db_type: &str = match db_type.find('(') {
+            Some(paren) => db_type[..paren].trim(),
+            None => &db_type,
+        };
+        match db_type {
+            "boolean" | "bool" => {
+                (vec![], Type::Bool)
+            }
+            "uuid" => {
+                (vec!["uuid::Uuid".to_owned()], Type::Uuid)
+            }
+            "json" | "jsonb" => {
+                (vec!["rustc_serialize::json::Json".to_owned()], Type::Json)
+            }
+            "timestamp with time zone" | "timestamptz" => {
+                (vec!["chrono::datetime::DateTime".to_owned(),
+                      "chrono::offset::utc::UTC".to_owned()],
+                 Type::DateTime)
+            }
+            "timestamp" | "timestamp without time zone" | "datetime" => {
+                (vec!["chrono::naive::datetime::NaiveDateTime".to_owned()],
+                 Type::NaiveDateTime)
+            }
+            "date" => {
+                (vec!["chrono::naive::date::NaiveDate".to_owned()],
+                 Type::NaiveDate)
+            }
+            "time" | "time with time zone" | "time without time zone" => {
+                (vec!["chrono::naive::time::NaiveTime".to_owned()],
+                 Type::NaiveTime)
+            }
+            _ if db_type.contains("int") => {
+                (vec![], Type::I64)
+            }
+            _ if db_type.contains("char") || db_type.contains("clob") || db_type.contains("text") => {
+                (vec![], Type::String)
+            }
+            _ if db_type.is_empty() || db_type.contains("blob") => {
+                (vec![], Type::VecU8)
+            }
+            _ => {
+                (vec![], Type::F64)
+            }
+        }
     }
 
-    fn rust_type_to_dbtype(&self, _rust_type: &Type) -> String {
-        unimplemented!()
+    fn rust_type_to_dbtype(&self, rust_type: &Type) -> String {
+        Sqlite::rust_type_to_dbtype(self, rust_type)
     }
 }
 
@@ -596,3 +1289,21 @@ CREATE TABLE product_availability (
     ";
     let _ = Sqlite::extract_comments(create_sql);
 }
+
+#[test]
+fn test_error_info() {
+    assert_eq!(error_info(2067, "UNIQUE constraint failed: product.name"),
+               (ErrorKind::UniqueViolation, None, Some("product".to_owned())));
+    assert_eq!(error_info(19, "NOT NULL constraint failed: product.price"),
+               (ErrorKind::NotNullViolation, None, Some("product".to_owned())));
+    assert_eq!(error_info(19, "CHECK constraint failed: price_positive"),
+               (ErrorKind::CheckViolation, Some("price_positive".to_owned()), None));
+    assert_eq!(error_info(787, "FOREIGN KEY constraint failed"),
+               (ErrorKind::ForeignKeyViolation, None, None));
+    assert_eq!(error_info(1, "no such table: products"),
+               (ErrorKind::UndefinedTable, None, Some("products".to_owned())));
+    assert_eq!(error_info(1, "near \"SELEC\": syntax error"),
+               (ErrorKind::SyntaxError, None, None));
+    assert_eq!(error_info(5, "database is locked"),
+               (ErrorKind::LockTimeout, None, None));
+}
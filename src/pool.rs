@@ -1,7 +1,7 @@
 use r2d2::Pool;
 use r2d2_postgres::PostgresConnectionManager;
 use r2d2::Config;
-use postgres::SslMode;
+use r2d2_postgres::SslMode;
 use config::DbConfig;
 use database::{Database, DatabaseDDL, DatabaseDev};
 use platform::Postgres;
@@ -15,6 +15,7 @@ use mysql::conn::pool::MyPool;
 use mysql::conn::MyOpts;
 use database::DbError;
 use std::ops::Deref;
+use transaction::Transaction;
 
 #[cfg(feature = "sqlite")]
 use r2d2_sqlite::SqliteConnectionManager;
@@ -53,6 +54,12 @@ impl Platform {
         }
     }
 
+    /// begin a transaction on this connection,
+    /// the transaction is rolled back when the returned guard is dropped without committing
+    pub fn transaction(&self) -> Result<Transaction, DbError> {
+        Transaction::new(self.as_ref())
+    }
+
     pub fn as_dev(&self) -> &DatabaseDev {
         match *self {
             Platform::Postgres(ref pg) => pg,
@@ -1,8 +1,10 @@
 use query::Query;
+use query::Error;
 use query::Modifier;
 use query::Direction;
 use query::Equality;
 use query::SqlType;
+use query::SetOperation;
 use query::Operand;
 use query::{ColumnName,ToColumnName};
 use query::NullsWhere;
@@ -57,7 +59,15 @@ impl QueryBuilder {
     /// wrapped in the from_query
     /// build a builder for this
     pub fn WITH(&mut self, query: Query, alias: &str) -> &mut Self {
-        self.query.declared_query.insert(alias.to_owned(), query);
+        self.query.declare(alias, query);
+        self
+    }
+
+    /// declare a recursive query, i.e WITH RECURSIVE alias AS (query UNION ALL recursive)
+    /// the recursive query refers to the alias as a table
+    /// only supported by databases that supports CTE
+    pub fn WITH_RECURSIVE(&mut self, query: Query, recursive: Query, alias: &str) -> &mut Self {
+        self.query.declare_recursive(alias, query, recursive);
         self
     }
 
@@ -97,6 +107,15 @@ impl QueryBuilder {
     }
 
 
+    /// add DISTINCT ON ie: SELECT DISTINCT ON (column1, column2)
+    /// only supported in postgresql
+    pub fn DISTINCT_ON(&mut self, columns: &[&str]) -> &mut Self {
+        for c in columns {
+            self.query.distinct_on_columns.push((*c).to_owned());
+        }
+        self
+    }
+
     pub fn ALL(&mut self) -> &mut Self {
         self.query.all();
         self
@@ -118,6 +137,13 @@ impl QueryBuilder {
          self
     }
 
+    /// enumerate fields, which can be renamed
+    pub fn FIELDS(&mut self, to_field: &ToField) -> &mut Self {
+        let mut fields = to_field.to_field();
+        self.query.enumerated_fields.append(&mut fields);
+        self
+    }
+
     pub fn VALUES(&mut self, to_values: &[&ToValue]) -> &mut Self{
         for tov in to_values{
             let v = tov.to_db_type();
@@ -135,12 +161,46 @@ impl QueryBuilder {
     } 
 
     
+    /// select from the result of the query, renamed as alias
+    pub fn FROM_QUERY(&mut self, query: Query, alias: &str) -> &mut Self {
+        self.query.from_query(query, alias);
+        self
+    }
+
     /// `into` is used in rust, os settled with `into_`
     pub fn INTO(&mut self, table: &ToTableName) -> &mut Self {
         assert_eq!(self.query.sql_type, SqlType::INSERT);
         self.TABLE(table);
         self
     }
+    /// the columns of the unique constraint to check for conflicts when inserting,
+    /// followed by either DO_NOTHING or DO_UPDATE
+    pub fn ON_CONFLICT(&mut self, columns: &[&str]) -> &mut Self {
+        assert_eq!(self.query.sql_type, SqlType::INSERT);
+        self.query.on_conflict(columns);
+        self
+    }
+
+    /// check for conflicts on the primary key of the table of this model
+    pub fn ON_CONFLICT_KEY<T: IsTable>(&mut self) -> &mut Self {
+        assert_eq!(self.query.sql_type, SqlType::INSERT);
+        self.query.on_conflict_key::<T>();
+        self
+    }
+
+    /// skip the conflicting records
+    pub fn DO_NOTHING(&mut self) -> &mut Self {
+        self.query.do_nothing();
+        self
+    }
+
+    /// update these columns of the conflicting records with the inserted values
+    /// when empty, all inserted columns except the conflict columns are updated
+    pub fn DO_UPDATE(&mut self, columns: &[&str]) -> &mut Self {
+        self.query.do_update(columns);
+        self
+    }
+
     /// can be used in behalf of into_, from,
     pub fn TABLE(&mut self, table: &ToTableName) -> &mut Self {
 		let table_name = table.to_table_name();
@@ -216,6 +276,33 @@ impl QueryBuilder {
     }
 
     
+    /// combine the result with the other select query, removing duplicates
+    /// the ORDER_BY, LIMIT and OFFSET of this builder applies to the combined result
+    pub fn UNION(&mut self, query: Query) -> &mut Self {
+        self.query.combine(SetOperation::UNION, query);
+        self
+    }
+
+    /// combine the result with the other select query, keeping duplicates
+    pub fn UNION_ALL(&mut self, query: Query) -> &mut Self {
+        self.query.combine(SetOperation::UNION_ALL, query);
+        self
+    }
+
+    /// only the records that are also returned by the other select query
+    /// not supported in mysql
+    pub fn INTERSECT(&mut self, query: Query) -> &mut Self {
+        self.query.combine(SetOperation::INTERSECT, query);
+        self
+    }
+
+    /// only the records that are not returned by the other select query
+    /// not supported in mysql
+    pub fn EXCEPT(&mut self, query: Query) -> &mut Self {
+        self.query.combine(SetOperation::EXCEPT, query);
+        self
+    }
+
 	pub fn ORDER_BY(&mut self, to_order: &ToOrder)->&mut Self {
 		let mut orders = to_order.to_order();
 		self.query.order_by.append(&mut orders);
@@ -233,8 +320,13 @@ impl QueryBuilder {
     }
 
 
+    /// the query being built, to be used in other queries
+    pub fn to_query(&self) -> Query {
+        self.query.clone()
+    }
+
     /// build the query only, not executed, useful when debugging
-    pub fn build(&mut self, db: &Database) -> SqlFrag {
+    pub fn build(&mut self, db: &Database) -> Result<SqlFrag, Error> {
         self.query.build(db)
     }
 
@@ -246,3 +338,16 @@ impl QueryBuilder {
 	}
 }
 
+/// the query being built is used as a subquery
+impl ToOperand for QueryBuilder {
+    fn to_operand(&self) -> Operand {
+        self.query.to_operand()
+    }
+}
+
+/// builder methods returns &mut QueryBuilder
+impl <'a>ToOperand for &'a mut QueryBuilder {
+    fn to_operand(&self) -> Operand {
+        self.query.to_operand()
+    }
+}
@@ -0,0 +1,117 @@
+use query::Operand;
+use query::Filter;
+use query::operand::ToOperand;
+use std::fmt;
+
+
+/// operators of the binary expressions
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Operator {
+    ADD,
+    SUB,
+    MUL,
+    DIV,
+    MOD,
+    /// string concatenation, `||` or CONCAT() depending on the database
+    CONCAT,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operator::ADD => write!(f, "+"),
+            Operator::SUB => write!(f, "-"),
+            Operator::MUL => write!(f, "*"),
+            Operator::DIV => write!(f, "/"),
+            Operator::MOD => write!(f, "%"),
+            Operator::CONCAT => write!(f, "||"),
+        }
+    }
+}
+
+/// an expression with left and right operand, ie: price * quantity
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct BinaryExpression {
+    pub left: Operand,
+    pub operator: Operator,
+    pub right: Operand,
+}
+
+/// CASE WHEN condition THEN result .. ELSE result END
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Case {
+    pub when_then: Vec<(Filter, Operand)>,
+    pub else_result: Option<Operand>,
+}
+
+impl Case {
+
+    pub fn WHEN(mut self, filter: Filter, result: &ToOperand) -> Self {
+        self.when_then.push((filter, result.to_operand()));
+        self
+    }
+
+    pub fn ELSE(mut self, result: &ToOperand) -> Self {
+        self.else_result = Some(result.to_operand());
+        self
+    }
+
+    pub fn END(self) -> Operand {
+        Operand::Case(Box::new(self))
+    }
+}
+
+impl ToOperand for Case {
+    fn to_operand(&self) -> Operand {
+        Operand::Case(Box::new(self.clone()))
+    }
+}
+
+/// start a CASE expression, followed by WHEN, an optional ELSE then END
+pub fn CASE() -> Case {
+    Case {
+        when_then: vec![],
+        else_result: None,
+    }
+}
+
+/// arithmetic and concatenation of operands
+pub trait HasArithmetic {
+    fn ADD(&self, to_operand: &ToOperand) -> Operand;
+    fn SUB(&self, to_operand: &ToOperand) -> Operand;
+    fn MUL(&self, to_operand: &ToOperand) -> Operand;
+    fn DIV(&self, to_operand: &ToOperand) -> Operand;
+    fn MOD(&self, to_operand: &ToOperand) -> Operand;
+    fn CONCAT(&self, to_operand: &ToOperand) -> Operand;
+    fn NEG(&self) -> Operand;
+}
+
+macro_rules! fn_has_arithmetic{
+    ($f:ident, $op:expr) => (
+        fn $f(&self, to_operand: &ToOperand) -> Operand {
+            Operand::Binary(Box::new(BinaryExpression {
+                left: self.to_operand(),
+                operator: $op,
+                right: to_operand.to_operand(),
+            }))
+        }
+    )
+}
+
+impl <T>HasArithmetic for T where T: ToOperand {
+
+    fn_has_arithmetic!(ADD, Operator::ADD);
+    fn_has_arithmetic!(SUB, Operator::SUB);
+    fn_has_arithmetic!(MUL, Operator::MUL);
+    fn_has_arithmetic!(DIV, Operator::DIV);
+    fn_has_arithmetic!(MOD, Operator::MOD);
+    fn_has_arithmetic!(CONCAT, Operator::CONCAT);
+
+    fn NEG(&self) -> Operand {
+        Operand::Negate(Box::new(self.to_operand()))
+    }
+}
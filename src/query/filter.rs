@@ -6,6 +6,7 @@ use std::ops::BitAnd;
 use std::ops::BitOr;
 use query::operand::ToOperand;
 use dao::Type;
+use query::source::QuerySource;
 
 /// expression has left operand,
 /// equality and right operand
@@ -42,6 +43,8 @@ pub enum Equality {
     ILIKE, //add ILIKE
     IS_NOT_NULL, // NOT_NULL,
     IS_NULL, // IS_NULL,
+    EXISTS, // EXISTS (subquery)
+    NOT_EXISTS, // NOT EXISTS (subquery)
 }
 
 #[derive(Debug)]
@@ -69,6 +72,18 @@ impl Filter {
     }
 
 
+    /// finalize the subqueries used in this filter and its sub filters
+    pub fn finalize_subqueries(&mut self) {
+        for operand in vec![&mut self.condition.left, &mut self.condition.right] {
+            if let Operand::QuerySource(QuerySource::Query(ref mut query)) = *operand {
+                query.finalize();
+            }
+        }
+        for filter in &mut self.sub_filters {
+            filter.finalize_subqueries();
+        }
+    }
+
 	pub fn AND(mut self, filter: Filter)->Self{
 		let mut filter = filter.clone();
 		filter.connector = Connector::And;
@@ -171,3 +186,27 @@ impl <T>HasEquality for T where T:ToOperand{
 
 }
 
+macro_rules! fn_exists{
+	($(#[$attr:meta])* $f:ident, $eq: expr) => (
+		$(#[$attr])*
+		pub fn $f(query: &ToOperand)->Filter{
+			let cond = Condition{
+				left: query.to_operand(),
+				equality: $eq,
+				right: Operand::Value(Value::None(Type::String))
+			};
+			Filter{
+				connector: Connector::And,
+				condition:cond,
+				sub_filters: vec![]
+			}
+		}
+	)
+}
+
+fn_exists!(
+	/// filter the records for which the subquery returns any row
+	EXISTS, Equality::EXISTS);
+fn_exists!(
+	/// filter the records for which the subquery returns no row
+	NOT_EXISTS, Equality::NOT_EXISTS);
@@ -61,3 +61,75 @@ pub fn NOW()->Operand{
         )
     )
 }
+
+/// database function SUM
+pub fn SUM(to_operand: &ToOperand)->Operand{
+	Operand::QuerySource(
+		QuerySource::Function(
+			Function{
+				function: "SUM".to_owned(),
+				params: vec![to_operand.to_operand()]
+			}
+		)
+	)
+}
+
+/// window function which numbers the rows of the partition starting from 1
+pub fn ROW_NUMBER()->Operand{
+    Operand::QuerySource(
+        QuerySource::Function(
+            Function{
+                function: "ROW_NUMBER".to_owned(),
+                params: vec![],
+            }
+        )
+    )
+}
+
+/// window function which ranks the rows of the partition, with gaps for ties
+pub fn RANK()->Operand{
+    Operand::QuerySource(
+        QuerySource::Function(
+            Function{
+                function: "RANK".to_owned(),
+                params: vec![],
+            }
+        )
+    )
+}
+
+/// window function which ranks the rows of the partition, without gaps
+pub fn DENSE_RANK()->Operand{
+    Operand::QuerySource(
+        QuerySource::Function(
+            Function{
+                function: "DENSE_RANK".to_owned(),
+                params: vec![],
+            }
+        )
+    )
+}
+
+/// window function LAG, the value of the previous row in the partition
+pub fn LAG(to_operand: &ToOperand)->Operand{
+	Operand::QuerySource(
+		QuerySource::Function(
+			Function{
+				function: "LAG".to_owned(),
+				params: vec![to_operand.to_operand()]
+			}
+		)
+	)
+}
+
+/// window function LEAD, the value of the next row in the partition
+pub fn LEAD(to_operand: &ToOperand)->Operand{
+	Operand::QuerySource(
+		QuerySource::Function(
+			Function{
+				function: "LEAD".to_owned(),
+				params: vec![to_operand.to_operand()]
+			}
+		)
+	)
+}
@@ -1,6 +1,5 @@
 use dao::{Value, ToValue};
 use table::Table;
-use std::collections::BTreeMap;
 use database::Database;
 use dao::DaoResult;
 use dao::IsDao;
@@ -8,7 +7,10 @@ use dao::Dao;
 use table::IsTable;
 use writer::SqlFrag;
 use std::fmt;
+use std::error;
 use database::DbError;
+use database::StreamStatement;
+use std::marker::PhantomData;
 use database::BuildMode;
 
 pub mod column_name;
@@ -21,10 +23,13 @@ pub mod operand;
 pub mod order;
 pub mod field;
 pub mod source;
+pub mod window;
+pub mod expression;
 
 pub use self::column_name::{ColumnName,ToColumnName};
 pub use self::table_name::{TableName,ToTableName};
 pub use self::filter::{Filter,Condition,Equality,Connector,HasEquality};
+pub use self::filter::{EXISTS,NOT_EXISTS};
 pub use self::builder::QueryBuilder;
 pub use self::function::COUNT;
 pub use self::function::Function;
@@ -34,6 +39,8 @@ pub use self::order::{Order,ToOrder,HasDirection,NullsWhere,Direction};
 pub use self::field::{Field,ToField};
 pub use self::source::SourceField;
 pub use self::source::{QuerySource,ToSourceField};
+pub use self::window::{Window,WindowFunction,HasWindow,PARTITION_BY};
+pub use self::expression::{HasArithmetic,CASE};
 
 
 
@@ -52,14 +59,113 @@ pub enum SqlType {
 
 
 
-/// Query Error
+/// Query Error, when the query can not be built
+#[derive(Debug)]
 pub enum Error {
     NoTableSpecified(String),
     NoColumnSpecified(String),
     SqlError(String),
+    /// the feature used in the query is not supported by the database platform
+    UnsupportedFeature(String),
+    /// the number of values doesn't match the columns
+    ValuesMismatch(String),
+    /// the parts of the query can not be used together
+    InvalidQuery(String),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::NoTableSpecified(ref description) => description,
+            Error::NoColumnSpecified(ref description) => description,
+            Error::SqlError(ref description) => description,
+            Error::UnsupportedFeature(ref description) => description,
+            Error::ValuesMismatch(ref description) => description,
+            Error::InvalidQuery(ref description) => description,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", error::Error::description(self))
+    }
+}
+
+
+
+/// A query declared with a name in the WITH clause (common table expression)
+/// which can then be referred to as a table in the query
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct DeclaredQuery {
+    pub name: String,
+    /// the query, or the non-recursive term of a recursive query
+    pub query: Query,
+    /// the recursive term, which refers to the declared name,
+    /// combined with the non-recursive term with UNION ALL
+    pub recursive: Option<Query>,
+}
+
+impl DeclaredQuery {
+
+    pub fn is_recursive(&self) -> bool {
+        self.recursive.is_some()
+    }
+}
+
+/// the set operation used in combining the result of select queries
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum SetOperation {
+    UNION,
+    UNION_ALL,
+    INTERSECT,
+    EXCEPT,
+}
+
+/// what to do with the record being inserted
+/// when it conflicts with an existing record
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum ConflictAction {
+    /// skip the record being inserted
+    DoNothing,
+    /// update the listed columns of the existing record with the values being inserted,
+    /// when empty all the inserted columns except the conflict target are updated
+    DoUpdate(Vec<String>),
 }
 
+/// The ON CONFLICT clause of an insert (upsert)
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct OnConflict {
+    /// the conflict target, the columns of a unique constraint or primary key
+    /// mysql doesn't use this, any unique key conflict triggers the action
+    pub columns: Vec<String>,
+    pub action: ConflictAction,
+}
 
+impl OnConflict {
+
+    /// the columns to update when the record is updated
+    pub fn update_columns(&self, query: &Query) -> Vec<String> {
+        match self.action {
+            ConflictAction::DoNothing => vec![],
+            ConflictAction::DoUpdate(ref columns) if !columns.is_empty() => columns.clone(),
+            ConflictAction::DoUpdate(_) => {
+                query.get_enumerated_columns()
+                     .iter()
+                     .map(|c| c.column.to_owned())
+                     .filter(|c| !self.columns.contains(c))
+                     .collect()
+            }
+        }
+    }
+}
 
 #[derive(Debug)]
 #[derive(PartialEq)]
@@ -88,6 +194,23 @@ impl Range{
 
 }
 
+/// a statement of which the streamed rows are converted using `IsDao::try_from_dao`
+pub struct StreamAs<'a, T> {
+    statement: Box<StreamStatement + 'a>,
+    phantom: PhantomData<T>,
+}
+
+impl <'a, T: IsDao>StreamAs<'a, T> {
+
+    /// execute the statement, the records are returned one at a time as they are read from the database
+    pub fn rows<'s>(&'s mut self) -> Result<Box<Iterator<Item = Result<T, DbError>> + 's>, DbError>
+        where T: 's
+    {
+        let rows = try!(self.statement.rows());
+        Ok(Box::new(rows.map(|dao| dao.and_then(|dao| T::try_from_dao(&dao).map_err(DbError::from)))))
+    }
+}
+
 
 
 #[derive(Debug)]
@@ -103,7 +226,8 @@ pub struct Query {
     /// whether to enumate all columns in involved models
     pub enumerate_all: bool,
 
-    pub declared_query: BTreeMap<String, Query>,
+    /// queries declared in the WITH clause, in the order they are declared
+    pub declared_query: Vec<DeclaredQuery>,
 
     ///fields can be functions, column sql query, and even columns
     pub enumerated_fields:Vec<Field>,
@@ -146,6 +270,13 @@ pub struct Query {
 
     /// the returning clause of the query when supported,
     pub enumerated_returns: Vec<Field>,
+
+    /// what to do when the inserted record conflicts with an existing one
+    pub on_conflict: Option<OnConflict>,
+
+    /// select queries combined with this query, in order
+    /// the order by and range of this query applies to the combined result
+    pub combined: Vec<(SetOperation, Query)>,
 }
 
 impl Query {
@@ -156,7 +287,7 @@ impl Query {
             sql_type: SqlType::SELECT,
             distinct: false,
             enumerate_all: false,
-            declared_query: BTreeMap::new(),
+            declared_query: vec![],
             enumerated_fields: vec![],
             distinct_on_columns: vec![],
             filters: vec![],
@@ -169,6 +300,8 @@ impl Query {
             from: vec![],
             values: vec![],
             enumerated_returns: vec![],
+            on_conflict: None,
+            combined: vec![],
         }
     }
 
@@ -184,6 +317,54 @@ impl Query {
         q.sql_type = SqlType::INSERT;
         q
     }
+    /// insert multiple records of the same model in 1 statement,
+    /// use `on_conflict_key::<T>()` to update or skip existing records
+    pub fn insert_many<T: IsDao + IsTable>(records: &[T]) -> Self {
+        let daos: Vec<Dao> = records.iter().map(|r| r.to_dao()).collect();
+        Self::insert_daos(&T::table(), &daos)
+    }
+
+    /// insert multiple daos into the table in 1 statement,
+    /// the columns are aligned across the daos,
+    /// the column default is used for missing values in a dao
+    pub fn insert_daos(table: &Table, daos: &[Dao]) -> Self {
+        let mut q = Self::insert();
+        q.from(table);
+        let columns = Self::aligned_columns(table, daos);
+        for column in &columns {
+            q.column(column);
+        }
+        for dao in daos {
+            for column in &columns {
+                let value = match dao.values.get(column) {
+                    Some(value) => Operand::Value(value.clone()),
+                    None => Operand::Default,
+                };
+                q.values.push(value);
+            }
+        }
+        q
+    }
+
+    /// the columns used by any of the daos, in the order of the table columns
+    /// followed by the keys which are not known to the table
+    fn aligned_columns(table: &Table, daos: &[Dao]) -> Vec<String> {
+        let mut columns: Vec<String> = vec![];
+        for column in &table.columns {
+            if daos.iter().any(|dao| dao.values.contains_key(&column.name)) {
+                columns.push(column.name.clone());
+            }
+        }
+        for dao in daos {
+            for key in dao.values.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        columns
+    }
+
     pub fn update() -> Self {
         let mut q = Query::new();
         q.sql_type = SqlType::UPDATE;
@@ -285,6 +466,87 @@ impl Query {
     
 
 
+    /// combine the result of this query with the other select query
+    pub fn combine(&mut self, operation: SetOperation, query: Query){
+        self.combined.push((operation, query));
+    }
+
+    /// handle insert conflicts on these columns, skipping the conflicting records
+    /// unless `do_update` is called
+    pub fn on_conflict(&mut self, columns: &[&str]){
+        let on_conflict = OnConflict{
+            columns: columns.iter().map(|c| (*c).to_owned()).collect(),
+            action: ConflictAction::DoNothing,
+        };
+        self.on_conflict = Some(on_conflict);
+    }
+
+    /// handle insert conflicts on the primary key of the table
+    pub fn on_conflict_key<T: IsTable>(&mut self){
+        let table = T::table();
+        let columns: Vec<&str> = table.primary_columns().iter().map(|c| &c.name as &str).collect();
+        self.on_conflict(&columns);
+    }
+
+    /// skip the inserted records which conflicts with existing records
+    pub fn do_nothing(&mut self){
+        self.set_conflict_action(ConflictAction::DoNothing);
+    }
+
+    /// update these columns of the existing records with the inserted values on conflict
+    /// all inserted columns, except the conflict target are updated when no columns are specified
+    pub fn do_update(&mut self, columns: &[&str]){
+        let columns = columns.iter().map(|c| (*c).to_owned()).collect();
+        self.set_conflict_action(ConflictAction::DoUpdate(columns));
+    }
+
+    /// when there is no conflict target specified, the conflict is checked on any unique constraint
+    fn set_conflict_action(&mut self, action: ConflictAction){
+        match self.on_conflict {
+            Some(ref mut on_conflict) => on_conflict.action = action,
+            None => {
+                self.on_conflict = Some(OnConflict{
+                    columns: vec![],
+                    action: action,
+                })
+            }
+        }
+    }
+
+    /// declare a named query, rendered as WITH name AS (query)
+    /// in databases that support CTE, otherwise the query is inlined as a subquery
+    /// wherever the name is used as a table
+    pub fn declare(&mut self, name: &str, query: Query){
+        let declared = DeclaredQuery{
+            name: name.to_owned(),
+            query: query,
+            recursive: None,
+        };
+        self.declared_query.push(declared);
+    }
+
+    /// declare a recursive named query, rendered as
+    /// WITH RECURSIVE name AS (query UNION ALL recursive)
+    /// the recursive term refers to the declared name, ie: walking a tree of categories
+    pub fn declare_recursive(&mut self, name: &str, query: Query, recursive: Query){
+        let declared = DeclaredQuery{
+            name: name.to_owned(),
+            query: query,
+            recursive: Some(recursive),
+        };
+        self.declared_query.push(declared);
+    }
+
+    /// get the declared query with this name
+    pub fn get_declared_query(&self, name: &str) -> Option<&DeclaredQuery> {
+        for declared in &self.declared_query {
+            if declared.name == name {
+                return Some(declared);
+            }
+        }
+        None
+    }
+
     /// a query to query from
     /// use WITH (query) t1 SELECT from t1 declaration in postgresql, sqlite
     /// use SELECT FROM (query) in oracle, mysql, others
@@ -366,6 +628,27 @@ impl Query {
     /// do a select all
     pub fn finalize(&mut self) -> &Self {
 
+        for declared in &mut self.declared_query {
+            declared.query.finalize();
+            if let Some(ref mut recursive) = declared.recursive {
+                recursive.finalize();
+            }
+        }
+        for &mut (_, ref mut combined) in &mut self.combined {
+            combined.finalize();
+        }
+        for source_field in &mut self.from {
+            if let QuerySource::Query(ref mut query) = source_field.source {
+                query.finalize();
+            }
+        }
+        for filter in &mut self.filters {
+            filter.finalize_subqueries();
+        }
+        for filter in &mut self.having {
+            filter.finalize_subqueries();
+        }
+
         let involved_models = self.get_involved_tables();
         if involved_models.len() > 1 {
             //enumerate all columns when there is a join
@@ -526,13 +809,13 @@ impl Query {
     }
 
     /// build the query only, not executed, useful when debugging
-    pub fn build(&mut self, db: &Database) -> SqlFrag {
+    pub fn build(&mut self, db: &Database) -> Result<SqlFrag, Error> {
         self.finalize();
         db.build_query(self, BuildMode::Standard)
     }
 
 	/// Warning: don't use this in production
-	pub fn debug_build(&mut self, db: &Database)-> SqlFrag{
+	pub fn debug_build(&mut self, db: &Database)-> Result<SqlFrag, Error>{
 		self.finalize();
 		db.build_query(self, BuildMode::Debug)
 	}
@@ -552,6 +835,24 @@ impl Query {
         db.execute_with_one_return(self)
     }
 
+    /// prepare the query for its rows to be returned one at a time as they are read from the database,
+    /// so large results don't have to fit in memory
+    pub fn stream<'a>(&mut self, db: &'a Database) -> Result<Box<StreamStatement + 'a>, DbError> {
+        self.finalize();
+        db.stream(self)
+    }
+
+    /// prepare the query for its rows to be streamed converted using `IsDao::try_from_dao`
+    pub fn stream_as<'a, T>(&mut self, db: &'a Database) -> Result<StreamAs<'a, T>, DbError>
+        where T: IsDao
+    {
+        let statement = try!(self.stream(db));
+        Ok(StreamAs {
+            statement: statement,
+            phantom: PhantomData,
+        })
+    }
+
     /// delete, update without caring for the return
     pub fn execute(&mut self, db: &Database) -> Result<usize, DbError> {
         self.finalize();
@@ -564,6 +865,14 @@ impl Query {
         Ok(result.cast())
     }
 
+    /// execute the query, then convert the result using `IsDao::try_from_dao`,
+    /// missing or mismatched values are returned as an error instead of panicking
+    pub fn try_collect<T: IsDao + IsTable>(&mut self, db: &Database) -> Result<Vec<T>, DbError> {
+        let result = try!(self.retrieve(db));
+        let casted = try!(result.try_cast());
+        Ok(casted)
+    }
+
     /// execute the query then collect only 1 record
     pub fn collect_one<T: IsDao + IsTable>(&mut self, db: &Database) -> Result<T, DbError> {
         let result = try!(self.retrieve(db));
@@ -2,6 +2,9 @@ use query::ColumnName;
 use query::TableName;
 use query::Function;
 use query::Query;
+use query::Field;
+use query::window::WindowFunction;
+use query::expression::{BinaryExpression, Case};
 use dao::Value;
 use query::Filter;
 use table::Column;
@@ -35,6 +38,27 @@ pub enum Operand {
 	QuerySource(QuerySource),
     Value(Value),
     Vec(Vec<Operand>),
+    /// a function applied over a window
+    Window(Box<WindowFunction>),
+    /// arithmetic or concatenation of 2 operands
+    Binary(Box<BinaryExpression>),
+    /// the negative of the operand
+    Negate(Box<Operand>),
+    /// CASE WHEN .. THEN .. ELSE .. END
+    Case(Box<Case>),
+    /// the DEFAULT keyword, the column default is used as the value in an insert
+    Default,
+}
+
+impl Operand {
+
+    /// use this operand as a field renamed to `name`
+    pub fn AS(&self, name: &str) -> Field {
+        Field {
+            operand: self.clone(),
+            name: Some(name.to_owned()),
+        }
+    }
 }
 /// work around for &ToOperand argument for Operand
 impl ToOperand for Operand{
@@ -45,6 +69,13 @@ impl ToOperand for Operand{
 
 
 
+/// a query used as an operand is a subquery, ie: IN (SELECT ..)
+impl ToOperand for Query{
+	fn to_operand(&self)->Operand{
+		Operand::QuerySource(QuerySource::Query(self.clone()))
+	}
+}
+
 impl <F>ToOperand for F where F:Fn()->Column{
 	fn to_operand(&self)->Operand{
 		Operand::ColumnName(self().to_column_name())
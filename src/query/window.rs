@@ -0,0 +1,138 @@
+use query::Operand;
+use query::operand::ToOperand;
+use query::order::{Order, ToOrder};
+use std::fmt;
+
+
+/// whether the frame is counted in rows or in the range of values of the order by
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum FrameUnit {
+    ROWS,
+    RANGE,
+}
+
+/// the start or end of a window frame
+#[derive(Debug)]
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+pub enum FrameBound {
+    UNBOUNDED_PRECEDING,
+    PRECEDING(usize),
+    CURRENT_ROW,
+    FOLLOWING(usize),
+    UNBOUNDED_FOLLOWING,
+}
+
+impl fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameBound::UNBOUNDED_PRECEDING => write!(f, "UNBOUNDED PRECEDING"),
+            FrameBound::PRECEDING(n) => write!(f, "{} PRECEDING", n),
+            FrameBound::CURRENT_ROW => write!(f, "CURRENT ROW"),
+            FrameBound::FOLLOWING(n) => write!(f, "{} FOLLOWING", n),
+            FrameBound::UNBOUNDED_FOLLOWING => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// the set of rows of the partition the window function is applied to
+/// ie: ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Frame {
+    pub unit: FrameUnit,
+    pub start: FrameBound,
+    pub end: Option<FrameBound>,
+}
+
+/// The window definition in `OVER (PARTITION BY .. ORDER BY .. frame)`
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Window {
+    pub partition_by: Vec<Operand>,
+    pub order_by: Vec<Order>,
+    pub frame: Option<Frame>,
+}
+
+impl Window {
+
+    /// an empty window, the function is applied to all the rows
+    pub fn new() -> Self {
+        Window {
+            partition_by: vec![],
+            order_by: vec![],
+            frame: None,
+        }
+    }
+
+    pub fn PARTITION_BY(mut self, to_operand: &ToOperand) -> Self {
+        match to_operand.to_operand() {
+            Operand::Vec(operands) => {
+                for op in operands {
+                    self.partition_by.push(op);
+                }
+            }
+            operand => self.partition_by.push(operand),
+        }
+        self
+    }
+
+    pub fn ORDER_BY(mut self, to_order: &ToOrder) -> Self {
+        let mut orders = to_order.to_order();
+        self.order_by.append(&mut orders);
+        self
+    }
+
+    /// ROWS BETWEEN start AND end
+    pub fn ROWS(mut self, start: FrameBound, end: FrameBound) -> Self {
+        self.frame = Some(Frame {
+            unit: FrameUnit::ROWS,
+            start: start,
+            end: Some(end),
+        });
+        self
+    }
+
+    /// RANGE BETWEEN start AND end
+    pub fn RANGE(mut self, start: FrameBound, end: FrameBound) -> Self {
+        self.frame = Some(Frame {
+            unit: FrameUnit::RANGE,
+            start: start,
+            end: Some(end),
+        });
+        self
+    }
+}
+
+/// a function applied over a window, ie: ROW_NUMBER() OVER (PARTITION BY category ORDER BY price)
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct WindowFunction {
+    /// the function applied over the window,
+    /// other operands are rejected when the query is built
+    pub function: Operand,
+    pub window: Window,
+}
+
+/// start a window partitioned by these operands
+pub fn PARTITION_BY(to_operand: &ToOperand) -> Window {
+    Window::new().PARTITION_BY(to_operand)
+}
+
+/// apply the function over the window
+pub trait HasWindow {
+    fn OVER(&self, window: Window) -> Operand;
+}
+
+impl HasWindow for Operand {
+
+    /// only functions can be applied over a window,
+    /// building the query fails when this operand is not a function
+    fn OVER(&self, window: Window) -> Operand {
+        Operand::Window(Box::new(WindowFunction {
+            function: self.clone(),
+            window: window,
+        }))
+    }
+}
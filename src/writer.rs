@@ -8,7 +8,9 @@ pub struct SqlFrag {
     pub sql: String,
     pub params: Vec<Value>,
     pub sql_options: Vec<SqlOption>,
-	pub build_mode: BuildMode
+	pub build_mode: BuildMode,
+    /// how deep the subquery being written is nested in the statement, 0 when not in a subquery
+    pub subquery_depth: usize,
 }
 
 impl fmt::Display for SqlFrag {
@@ -37,6 +39,7 @@ impl SqlFrag {
             params: vec![],
             sql_options: sql_options,
 			build_mode: build_mode,
+            subquery_depth: 0,
         }
     }
 
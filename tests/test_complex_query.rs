@@ -42,7 +42,7 @@ fn test_select() {
          .ORDER_BY(&["product.name".ASC(), "product.created".DESC()])
          .LIMIT(100)
          .OFFSET(20)
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
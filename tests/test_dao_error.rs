@@ -0,0 +1,82 @@
+extern crate rustorm;
+
+use rustorm::dao::{Dao, DaoError, IsDao, Type, Value};
+
+
+#[test]
+fn test_try_get() {
+    let mut dao = Dao::new();
+    dao.set("name", &"apple".to_owned());
+    dao.set("price", &1.5f64);
+    dao.set_value("description", Value::None(Type::String));
+
+    let name: String = dao.try_get("name").unwrap();
+    assert_eq!(name, "apple");
+    let price: Result<i32, DaoError> = dao.try_get("price");
+    assert_eq!(price,
+               Err(DaoError::TypeMismatch {
+                   column: "price".to_owned(),
+                   expected: Type::I32,
+                   actual: Value::F64(1.5),
+               }));
+    let quantity: Result<i32, DaoError> = dao.try_get("quantity");
+    assert_eq!(quantity, Err(DaoError::NoSuchColumn("quantity".to_owned())));
+}
+
+#[test]
+fn test_try_get_opt() {
+    let mut dao = Dao::new();
+    dao.set_value("description", Value::None(Type::String));
+    dao.set("name", &"apple".to_owned());
+
+    let description: Option<String> = dao.try_get_opt("description").unwrap();
+    assert_eq!(description, None);
+    let missing: Option<String> = dao.try_get_opt("missing").unwrap();
+    assert_eq!(missing, None);
+    let name: Result<Option<bool>, DaoError> = dao.try_get_opt("name");
+    assert!(name.is_err());
+}
+
+#[test]
+fn test_try_remove() {
+    let mut dao = Dao::new();
+    dao.set("name", &"apple".to_owned());
+    let name: String = dao.try_remove("name").unwrap();
+    assert_eq!(name, "apple");
+    let again: Result<String, DaoError> = dao.try_remove("name");
+    assert_eq!(again, Err(DaoError::NoSuchColumn("name".to_owned())));
+}
+
+#[derive(Debug)]
+struct Fruit {
+    name: String,
+    price: f64,
+}
+
+impl IsDao for Fruit {
+    fn try_from_dao(dao: &Dao) -> Result<Self, DaoError> {
+        Ok(Fruit {
+            name: try!(dao.try_get("name")),
+            price: try!(dao.try_get("price")),
+        })
+    }
+
+    fn to_dao(&self) -> Dao {
+        let mut dao = Dao::new();
+        dao.set("name", &self.name);
+        dao.set("price", &self.price);
+        dao
+    }
+}
+
+#[test]
+fn test_try_from_dao() {
+    let mut dao = Dao::new();
+    dao.set("name", &"apple".to_owned());
+    let fruit = Fruit::try_from_dao(&dao);
+    assert_eq!(fruit.unwrap_err(), DaoError::NoSuchColumn("price".to_owned()));
+    dao.set("price", &1.5f64);
+    let fruit = Fruit::from_dao(&dao);
+    assert_eq!(fruit.name, "apple");
+    assert_eq!(fruit.price, 1.5);
+}
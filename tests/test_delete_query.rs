@@ -28,7 +28,7 @@ fn test_delete() {
 
     let frag = DELETE().FROM(&"bazaar.product")
             .WHERE("product_id".EQ(&10001))
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 DELETE FROM bazaar.product
@@ -25,7 +25,7 @@ fn test_2tables() {
     let db = pool.connect().unwrap();
 
     let frag = SELECT_ALL().FROM(&["bazaar.product","bazaar.product_photo"])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
  SELECT *
@@ -45,7 +45,7 @@ fn test_3tables() {
     let db = pool.connect().unwrap();
 
     let frag = SELECT_ALL().FROM(&["bazaar.product","bazaar.product_photo", "photo"])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
  SELECT *
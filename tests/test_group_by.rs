@@ -26,7 +26,7 @@ fn test_group_by() {
 
     let frag = SELECT_ALL().FROM(&"bazaar.product")
          .GROUP_BY(&["product.name", "product.created"])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
   SELECT *
@@ -28,7 +28,7 @@ fn test_having() {
          .WHERE( "product.name".EQ(&12123))
          .GROUP_BY(&["category.name"])
          .HAVING(COUNT(&"*").GT(&1).AND(COUNT(&"*").EQ(&0)))
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
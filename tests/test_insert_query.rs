@@ -29,7 +29,7 @@ fn test_insert() {
     let frag = INSERT().INTO(&"bazaar.product")
             .COLUMNS(&[&"name", &"description", &"active"])
             .VALUES(&[&"GoPro".to_owned(), &"A nice camera".to_owned(), &true])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 INSERT INTO bazaar.product( name, description, active ) 
@@ -35,7 +35,7 @@ fn test_left_join() {
 		 		.ON("product.product_id".EQ(&"product_photo.product_id")))
          .LEFT_JOIN("bazaar.photo" 
 		 		.ON("product_photo.photo_id".EQ(&"photo.photo_id")))
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
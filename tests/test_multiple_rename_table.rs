@@ -26,7 +26,7 @@ fn test_table_rename() {
     let db = pool.connect().unwrap();
 
     let frag = SELECT_ALL().FROM(&"bazaar.product".AS("b_prod"))
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
@@ -46,7 +46,7 @@ fn test_multiple_table_renames() {
     let db = pool.connect().unwrap();
 
     let frag = SELECT_ALL().FROM(&["bazaar.product".AS("b_prod"), "bazaar.product_photo".AS("photos")])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
  SELECT *
@@ -69,7 +69,7 @@ fn test_multiple_3table_renames() {
 			.FROM(&["bazaar.product".AS("b_prod"), 
 					"bazaar.product_photo".AS("prod_photos"),
 					"bazaar.photo".AS("photos")])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
@@ -26,7 +26,7 @@ fn test_order_by() {
 
     let frag = SELECT_ALL().FROM(&"bazaar.product")
          .ORDER_BY(&["product.name".ASC().NULLS_FIRST(), "product.created".DESC().NULLS_LAST()])
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
 SELECT *
@@ -0,0 +1,147 @@
+#![cfg(feature = "sqlite")]
+extern crate rustorm;
+
+use rustorm::pool::ManagedPool;
+use rustorm::table::{Table, Column, Foreign};
+use rustorm::dao::Type;
+use rustorm::schema;
+
+
+fn column(name: &str, data_type: Type) -> Column {
+    Column {
+        table: None,
+        name: name.to_owned(),
+        data_type: data_type,
+        db_data_type: "".to_owned(),
+        is_primary: false,
+        is_unique: false,
+        default: None,
+        comment: None,
+        not_null: false,
+        foreign: None,
+        is_inherited: false,
+    }
+}
+
+fn table(name: &str, columns: Vec<Column>) -> Table {
+    Table {
+        name: name.to_owned(),
+        columns: columns,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_build_create_table() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let mut id = column("id", Type::I64);
+    id.is_primary = true;
+    let mut name = column("name", Type::String);
+    name.not_null = true;
+    name.is_unique = true;
+    let frag = db.as_ddl().build_create_table(&table("owner", vec![id, name]), true);
+    let expected = "
+CREATE TABLE IF NOT EXISTS owner (
+    id integer,
+    name text NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (name)
+)";
+    println!("actual:   {{\n{}}} [{}]", frag.sql, frag.sql.len());
+    println!("expected: {{{}}} [{}]", expected, expected.len());
+    assert!(frag.sql.trim() == expected.trim());
+}
+
+#[test]
+fn test_rebuild_with_foreign_constraint() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let mut id = column("id", Type::I64);
+    id.is_primary = true;
+    let owner = table("owner", vec![id.clone()]);
+    let mut item = table("item", vec![id, column("owner_id", Type::I64)]);
+    db.as_ddl().create_table(&owner, false).unwrap();
+    db.as_ddl().create_table(&item, false).unwrap();
+    db.as_ref().execute_sql("INSERT INTO owner (id) VALUES (1)", &[]).unwrap();
+    db.as_ref().execute_sql("INSERT INTO item (id, owner_id) VALUES (10, 1)", &[]).unwrap();
+
+    item.columns[1].foreign = Some(Foreign {
+        schema: None,
+        table: "owner".to_owned(),
+        column: "id".to_owned(),
+    });
+    db.as_ddl().set_foreign_constraint(&item).unwrap();
+
+    let foreign_keys = db.as_ref().execute_sql_with_return("PRAGMA foreign_key_list(item)", &[]).unwrap();
+    assert_eq!(foreign_keys.len(), 1);
+    let items = db.as_ref().execute_sql_with_return("SELECT * FROM item", &[]).unwrap();
+    assert_eq!(items.len(), 1);
+}
+
+#[test]
+fn test_rebuild_in_transaction_with_foreign_keys() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let mut id = column("id", Type::I64);
+    id.is_primary = true;
+    let owner = table("owner", vec![id.clone()]);
+    let mut item = table("item", vec![id, column("owner_id", Type::I64)]);
+    db.as_ddl().create_table(&owner, false).unwrap();
+    db.as_ddl().create_table(&item, false).unwrap();
+    db.as_ref().execute_batch("PRAGMA foreign_keys = ON").unwrap();
+
+    item.columns[1].foreign = Some(Foreign {
+        schema: None,
+        table: "owner".to_owned(),
+        column: "id".to_owned(),
+    });
+    db.as_ref().begin().unwrap();
+    assert!(db.as_ddl().set_foreign_constraint(&item).is_err());
+    db.as_ref().rollback().unwrap();
+
+    let foreign_keys = db.as_ref().execute_sql_with_return("PRAGMA foreign_key_list(item)", &[]).unwrap();
+    assert_eq!(foreign_keys.len(), 0);
+    let frag = db.as_ddl().build_add_foreign_constraint(&item, &item.columns[1]);
+    assert!(frag.sql.starts_with("-- execute outside of a transaction"));
+}
+
+#[test]
+fn test_diff_drop_foreign_key_and_add_column() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let mut id = column("id", Type::I64);
+    id.is_primary = true;
+    let owner = table("owner", vec![id.clone()]);
+    let mut owner_id = column("owner_id", Type::I64);
+    owner_id.foreign = Some(Foreign {
+        schema: None,
+        table: "owner".to_owned(),
+        column: "id".to_owned(),
+    });
+    let actual = table("item", vec![id.clone(), owner_id]);
+    db.as_ddl().create_table(&owner, false).unwrap();
+    db.as_ddl().create_table(&actual, false).unwrap();
+    db.as_ref().execute_sql("INSERT INTO owner (id) VALUES (1)", &[]).unwrap();
+    db.as_ref().execute_sql("INSERT INTO item (id, owner_id) VALUES (10, 1)", &[]).unwrap();
+
+    let expected = table("item", vec![id, column("owner_id", Type::I64), column("note", Type::String)]);
+    let diff = schema::diff(&[expected], &[actual]);
+    for frag in diff.to_sql(db.as_ddl()) {
+        db.as_ref().execute_batch(&frag.sql).unwrap();
+    }
+
+    let foreign_keys = db.as_ref().execute_sql_with_return("PRAGMA foreign_key_list(item)", &[]).unwrap();
+    assert_eq!(foreign_keys.len(), 0);
+    let items = db.as_ref().execute_sql_with_return("SELECT id, owner_id, note FROM item", &[]).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].get::<i64>("owner_id"), 1);
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "sqlite")]
+extern crate rustorm;
+
+use rustorm::dao::{Dao, IsDao, DaoError, Value};
+use rustorm::pool::ManagedPool;
+use rustorm::query::Query;
+use rustorm::database::{Database, DbError};
+
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+struct Note {
+    note_id: i64,
+    body: Option<String>,
+}
+
+impl IsDao for Note {
+    fn try_from_dao(dao: &Dao) -> Result<Self, DaoError> {
+        Ok(Note {
+            note_id: try!(dao.try_get("note_id")),
+            body: try!(dao.try_get_opt("body")),
+        })
+    }
+
+    fn to_dao(&self) -> Dao {
+        let mut dao = Dao::new();
+        dao.set("note_id", &self.note_id);
+        match self.body {
+            Some(ref body) => dao.set("body", body),
+            None => dao.set_null("body"),
+        }
+        dao
+    }
+}
+
+#[test]
+fn test_stream_as_sqlite() {
+    let pool = ManagedPool::init("sqlite://:memory:", 1).unwrap();
+    let db = pool.connect().unwrap();
+    db.execute_batch("CREATE TABLE note(note_id integer PRIMARY KEY, body text);
+                      INSERT INTO note VALUES (1, 'first');
+                      INSERT INTO note VALUES (2, NULL);")
+      .unwrap();
+
+    let mut query = Query::select();
+    query.all();
+    query.from(&"note");
+    let mut statement = query.stream_as::<Note>(db.as_ref()).unwrap();
+    let notes: Result<Vec<Note>, DbError> = statement.rows().unwrap().collect();
+    assert_eq!(notes.unwrap(),
+               vec![Note {
+                        note_id: 1,
+                        body: Some("first".to_owned()),
+                    },
+                    Note {
+                        note_id: 2,
+                        body: None,
+                    }]);
+}
+
+#[test]
+fn test_stream_statement_executed_again() {
+    let pool = ManagedPool::init("sqlite://:memory:", 1).unwrap();
+    let db = pool.connect().unwrap();
+    db.execute_batch("CREATE TABLE note(note_id integer PRIMARY KEY, body text);
+                      INSERT INTO note VALUES (1, 'first');
+                      INSERT INTO note VALUES (2, 'second');")
+      .unwrap();
+
+    let mut statement = db.stream_sql("SELECT body FROM note WHERE note_id >= ?1 ORDER BY note_id",
+                                      &[Value::I64(1)])
+                          .unwrap();
+    {
+        let mut rows = statement.rows().unwrap();
+        assert_eq!(rows.next().unwrap().unwrap().get::<String>("body"), "first");
+    }
+    let mut rows = statement.rows().unwrap();
+    let bodies: Result<Vec<Dao>, DbError> = rows.by_ref().collect();
+    assert_eq!(bodies.unwrap().len(), 2);
+    // the statement is not restarted once its rows are exhausted
+    assert!(rows.next().is_none());
+}
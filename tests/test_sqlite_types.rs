@@ -0,0 +1,60 @@
+#![cfg(feature = "sqlite")]
+extern crate rustorm;
+
+use rustorm::pool::ManagedPool;
+use rustorm::dao::{Type, Value};
+use rustorm::query::builder::SELECT_ALL;
+
+
+#[test]
+fn test_bind_storage_class() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let params = vec![Value::I32(1),
+                      Value::U64(u64::max_value()),
+                      Value::F32(1.5),
+                      Value::Bool(true),
+                      Value::VecU8(vec![1, 2, 3]),
+                      Value::None(Type::I64)];
+    let sql = "SELECT typeof($1) AS a, typeof($2) AS b, typeof($3) AS c, \
+               typeof($4) AS d, typeof($5) AS e, typeof($6) AS f";
+    let dao = db.as_ref().execute_sql_with_one_return(sql, &params).unwrap().unwrap();
+    let storage: Vec<String> = ["a", "b", "c", "d", "e", "f"]
+                                   .iter()
+                                   .map(|c| dao.get(c))
+                                   .collect();
+    assert_eq!(storage, vec!["integer", "text", "real", "integer", "blob", "null"]);
+}
+
+#[test]
+fn test_decode_declared_types() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    db.as_ref().execute_sql("CREATE TABLE item (id integer, price real, available boolean, \
+                             added date, photo blob, note text)", &[]).unwrap();
+    db.as_ref().execute_sql("INSERT INTO item VALUES (1, 2.5, 1, '2016-02-29', x'0102', NULL)", &[]).unwrap();
+
+    let dao = SELECT_ALL().FROM(&"item").to_query()
+                          .retrieve_one(db.as_ref()).unwrap().unwrap();
+    assert_eq!(dao.as_ref("id"), &Value::I64(1));
+    assert_eq!(dao.as_ref("price"), &Value::F64(2.5));
+    assert_eq!(dao.as_ref("available"), &Value::Bool(true));
+    assert_eq!(dao.as_ref("added").get_type(), Type::NaiveDate);
+    assert_eq!(dao.as_ref("photo"), &Value::VecU8(vec![1, 2]));
+    assert_eq!(dao.as_ref("note"), &Value::None(Type::String));
+}
+
+#[test]
+fn test_dev_column_types() {
+    let url = "sqlite://:memory:";
+    let pool = ManagedPool::init(&url, 1).unwrap();
+    let db = pool.connect().unwrap();
+
+    let dev = db.as_dev();
+    assert_eq!(dev.rust_type_to_dbtype(&Type::U64), "integer");
+    assert_eq!(dev.rust_type_to_dbtype(&Type::Object), "json");
+}
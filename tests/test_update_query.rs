@@ -31,7 +31,7 @@ fn test_update() {
             .SET("description", &"A nice camera".to_owned())
             .SET("active", &true)
             .WHERE("product_id".EQ(&10001))
-    	 .build(db.as_ref());
+    	 .build(db.as_ref()).unwrap();
 
     let expected = "
  UPDATE bazaar.product